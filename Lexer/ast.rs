@@ -0,0 +1,130 @@
+// Syntax tree of a toy-language file, as built by parser.rs. Operators are
+// kept as their Token (ADD, LE, ADD_ASSIGN, ...) instead of a second enum and
+// names as interned Symbols.
+use crate::intern::{Interner, Symbol};
+use crate::{Span, Token, unlex};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Type {
+    Int32,
+    Flt32,
+    Char,
+    String,
+    Bool,
+    Void,
+    Named(Symbol), //a struct
+}
+
+// the type as written in source
+impl Type {
+    pub fn text<'a>(&self, interner: &'a Interner) -> &'a str {
+        match self {
+            Type::Int32 => "int32",
+            Type::Flt32 => "flt32",
+            Type::Char => "char",
+            Type::String => "string",
+            Type::Bool => "bool",
+            Type::Void => "void",
+            Type::Named(name) => interner.get(*name),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Program {
+    pub items: Vec<Item>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Item {
+    pub kind: ItemKind,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ItemKind {
+    Func { name: Symbol, params: Vec<Param>, ret: Option<Type>, body: Block },
+    Struct { name: Symbol, fields: Vec<Param> },
+    Import(Symbol),
+    Global(Stmt), //always a StmtKind::Let
+}
+
+// a function parameter or struct field
+#[derive(Debug, Clone, PartialEq)]
+pub struct Param {
+    pub name: Symbol,
+    pub ty: Type,
+    pub span: Span,
+}
+
+// statements between `[ ]` or `{ }`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Block {
+    pub stmts: Vec<Stmt>,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Stmt {
+    pub kind: StmtKind,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum StmtKind {
+    Let { name: Symbol, ty: Option<Type>, value: Option<Expr> },
+    Assign { target: Expr, op: Token, value: Expr }, //op is ASSIGN or one of the compound assignments
+    If { cond: Expr, then_block: Block, else_block: Option<Block> }, //`else if` is an else block holding the if
+    While { cond: Expr, body: Block },
+    Return(Option<Expr>),
+    Print(Vec<Expr>),
+    Block(Block),
+    Expr(Expr),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Expr {
+    pub kind: ExprKind,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ExprKind {
+    Int(i32),
+    Float(f32),
+    Char(char),
+    Str(Symbol),
+    Bool(bool),
+    Var(Symbol),
+    Unary { op: Token, operand: Box<Expr> },
+    Binary { op: Token, left: Box<Expr>, right: Box<Expr> },
+    Call { callee: Box<Expr>, args: Vec<Expr> },
+    Field { object: Box<Expr>, field: Symbol },
+}
+
+// source text of an expression, nested binary operations in parentheses
+impl Expr {
+    pub fn text(&self, interner: &Interner) -> String {
+        let operand = |e: &Expr| match e.kind {
+            ExprKind::Binary { .. } => format!("({})", e.text(interner)),
+            _ => e.text(interner),
+        };
+        match &self.kind {
+            ExprKind::Int(value) => value.to_string(),
+            ExprKind::Float(value) => unlex::spelling(&Token::LIT_FLT32(*value), interner),
+            ExprKind::Char(value) => unlex::spelling(&Token::LIT_CHAR(*value), interner),
+            ExprKind::Str(value) => unlex::spelling(&Token::LIT_STRING(*value), interner),
+            ExprKind::Bool(value) => value.to_string(),
+            ExprKind::Var(name) => interner.get(*name).to_string(),
+            ExprKind::Unary { op, operand: inner } => format!("{}{}", unlex::spelling(op, interner), operand(inner)),
+            ExprKind::Binary { op, left, right } => {
+                format!("{} {} {}", operand(left), unlex::spelling(op, interner), operand(right))
+            }
+            ExprKind::Call { callee, args } => {
+                let args: Vec<String> = args.iter().map(|a| a.text(interner)).collect();
+                format!("{}({})", operand(callee), args.join(", "))
+            }
+            ExprKind::Field { object, field } => format!("{}.{}", operand(object), interner.get(*field)),
+        }
+    }
+}
@@ -0,0 +1,132 @@
+// Lexer and parser benchmark on a large generated program. Built with
+// `--features count-allocations`, a thin wrapper around the system allocator
+// also counts heap allocations, so `cargo run -- bench` can show what lexing
+// costs besides time.
+use crate::config::LexerConfig;
+use crate::intern::Interner;
+use crate::fuzz::Rng;
+use crate::source::FileId;
+use crate::{Lexer, Token, parser};
+use std::time::{Duration, Instant};
+
+#[cfg(feature = "count-allocations")]
+mod counting {
+    use std::alloc::{GlobalAlloc, Layout, System};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    struct CountingAlloc;
+
+    static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+    static ALLOCATED_BYTES: AtomicUsize = AtomicUsize::new(0);
+
+    // allocations and bytes so far
+    pub fn counts() -> Option<(usize, usize)> {
+        Some((ALLOCATIONS.load(Ordering::Relaxed), ALLOCATED_BYTES.load(Ordering::Relaxed)))
+    }
+
+    unsafe impl GlobalAlloc for CountingAlloc {
+        unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+            ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+            ALLOCATED_BYTES.fetch_add(layout.size(), Ordering::Relaxed);
+            unsafe { System.alloc(layout) }
+        }
+
+        unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+            unsafe { System.dealloc(ptr, layout) }
+        }
+
+        // a growing Vec or String counts once per reallocation
+        unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+            ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+            ALLOCATED_BYTES.fetch_add(new_size, Ordering::Relaxed);
+            unsafe { System.realloc(ptr, layout, new_size) }
+        }
+    }
+
+    #[global_allocator]
+    static GLOBAL: CountingAlloc = CountingAlloc;
+}
+
+#[cfg(not(feature = "count-allocations"))]
+mod counting {
+    pub fn counts() -> Option<(usize, usize)> {
+        None
+    }
+}
+
+// a program of `functions` functions drawing on a small pool of names, the
+// way real code keeps reusing the same identifiers
+pub fn generate_program(functions: usize, seed: u64) -> String {
+    const NAMES: [&str; 12] =
+        ["count", "total", "index", "value", "result", "left", "right", "node", "size", "limit", "step", "acc"];
+    let mut rng = Rng(seed.max(1));
+    let mut program = String::from("struct Point\n[\n  x : int32,\n  y : flt32\n]\n\n");
+    for f in 0..functions {
+        let a = NAMES[rng.below(NAMES.len())];
+        let b = NAMES[rng.below(NAMES.len())];
+        program.push_str(&format!("func compute{}({} : int32, p : Point) -> int32\n[\n", f, a));
+        program.push_str(&format!("  let {}_{} : int32 = {} * {};\n", b, f, a, rng.below(100)));
+        program.push_str(&format!("  while {} < {} [\n", a, rng.below(1000)));
+        program.push_str(&format!("    {} += p.x + {}.5;\n", a, rng.below(10)));
+        program.push_str(&format!("    if {} % 2 == 0 [ print \"even\", {}; ] else [ print \"odd\"; ]\n", a, a));
+        program.push_str("  ]\n");
+        program.push_str(&format!("  return compute{}({}, p) - {}_{};\n]\n\n", f / 2, a, b, f));
+    }
+    program
+}
+
+pub struct Measurement {
+    pub elapsed: Duration,
+    pub allocations: Option<(usize, usize)>, //count and bytes, None without count-allocations
+}
+
+fn measure(run: impl FnOnce()) -> Measurement {
+    let before = counting::counts();
+    let start = Instant::now();
+    run();
+    let elapsed = start.elapsed();
+    let allocations = before.zip(counting::counts()).map(|((count, bytes), (count_after, bytes_after))| {
+        (count_after - count, bytes_after - bytes)
+    });
+    Measurement { elapsed, allocations }
+}
+
+pub struct Report {
+    pub tokens: usize,
+    pub lex: Measurement,
+    pub parse: Measurement,
+}
+
+// lexes the way the parser does, one token at a time reading it through
+// curr(), then parses the whole program
+pub fn run(program: &str) -> Report {
+    let mut tokens = 0;
+    let mut lexer = Lexer::new();
+    lexer.reset(program.to_string(), 0);
+    let lex = measure(|| {
+        loop {
+            lexer.advance();
+            tokens += 1;
+            if lexer.curr() == Token::EOI {
+                break;
+            }
+        }
+    });
+    let parse = measure(|| {
+        let (_, errors) = parser::parse(program, FileId::default(), &LexerConfig::default(), &mut Interner::new());
+        assert!(errors.is_empty(), "the generated program has syntax errors: {:?}", errors);
+    });
+    Report { tokens, lex, parse }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn generated_program_parses() {
+        let program = generate_program(20, 3);
+        let report = run(&program);
+        assert_eq!(report.tokens, Lexer::tokenize(&program, &mut Interner::new()).unwrap().len());
+    }
+}
@@ -0,0 +1,198 @@
+// Control-flow graphs built straight from each function's AST. Straight-line
+// statements are grouped into basic blocks; `if` and `while` end a block with
+// a branch on their condition and `return` ends it for good.
+use crate::ast::*;
+use crate::intern::Symbol;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Exit {
+    Goto(usize),
+    Branch { cond: Expr, then: usize, other: usize },
+    Return(Option<Expr>),
+    End, //falls off the end of the function
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct BasicBlock {
+    pub stmts: Vec<Stmt>, //lets, assignments, prints and expression statements
+    pub exit: Exit,
+}
+
+// blocks[0] is the entry
+#[derive(Debug, Clone, PartialEq)]
+pub struct FunctionCfg {
+    pub name: Symbol,
+    pub blocks: Vec<BasicBlock>,
+}
+
+pub fn function_cfgs(program: &Program) -> Vec<FunctionCfg> {
+    let mut cfgs = Vec::new();
+    for item in &program.items {
+        if let ItemKind::Func { name, body, .. } = &item.kind {
+            cfgs.push(function_cfg(*name, body));
+        }
+    }
+    cfgs
+}
+
+pub fn function_cfg(name: Symbol, body: &Block) -> FunctionCfg {
+    let mut builder = Builder { blocks: Vec::new() };
+    let entry = builder.new_block();
+    builder.stmts(&body.stmts, entry);
+    FunctionCfg { name, blocks: builder.finish() }
+}
+
+struct Builder {
+    blocks: Vec<BasicBlock>,
+}
+
+impl Builder {
+    fn new_block(&mut self) -> usize {
+        self.blocks.push(BasicBlock { stmts: Vec::new(), exit: Exit::End });
+        self.blocks.len() - 1
+    }
+
+    // adds `stmts` to the graph starting in block `current`, returns the
+    // block control is in afterwards
+    fn stmts(&mut self, stmts: &[Stmt], mut current: usize) -> usize {
+        for stmt in stmts {
+            current = self.stmt(stmt, current);
+        }
+        current
+    }
+
+    fn stmt(&mut self, stmt: &Stmt, current: usize) -> usize {
+        match &stmt.kind {
+            StmtKind::Block(block) => self.stmts(&block.stmts, current),
+            StmtKind::If { cond, then_block, else_block } => {
+                let then_start = self.new_block();
+                let then_end = self.stmts(&then_block.stmts, then_start);
+                let other = else_block.as_ref().map(|block| {
+                    let start = self.new_block();
+                    (start, self.stmts(&block.stmts, start))
+                });
+                let join = self.new_block();
+                self.blocks[then_end].exit = Exit::Goto(join);
+                if let Some((_, other_end)) = other {
+                    self.blocks[other_end].exit = Exit::Goto(join);
+                }
+                let other = other.map_or(join, |(start, _)| start);
+                self.blocks[current].exit = Exit::Branch { cond: cond.clone(), then: then_start, other };
+                join
+            }
+            StmtKind::While { cond, body } => {
+                let test = self.new_block();
+                self.blocks[current].exit = Exit::Goto(test);
+                let body_start = self.new_block();
+                let body_end = self.stmts(&body.stmts, body_start);
+                self.blocks[body_end].exit = Exit::Goto(test);
+                let after = self.new_block();
+                self.blocks[test].exit = Exit::Branch { cond: cond.clone(), then: body_start, other: after };
+                after
+            }
+            StmtKind::Return(value) => {
+                self.blocks[current].exit = Exit::Return(value.clone());
+                // whatever follows is unreachable but still drawn
+                self.new_block()
+            }
+            _ => {
+                self.blocks[current].stmts.push(stmt.clone());
+                current
+            }
+        }
+    }
+
+    // drops the empty blocks nothing jumps to, which `return` leaves behind
+    fn finish(self) -> Vec<BasicBlock> {
+        let mut targeted = vec![false; self.blocks.len()];
+        targeted[0] = true;
+        for block in &self.blocks {
+            match block.exit {
+                Exit::Goto(to) => targeted[to] = true,
+                Exit::Branch { then, other, .. } => {
+                    targeted[then] = true;
+                    targeted[other] = true;
+                }
+                _ => {}
+            }
+        }
+        let keep: Vec<bool> = self
+            .blocks
+            .iter()
+            .zip(&targeted)
+            .map(|(block, &targeted)| targeted || !block.stmts.is_empty())
+            .collect();
+        let mut new_index = Vec::new();
+        let mut next = 0;
+        for &kept in &keep {
+            new_index.push(next);
+            next += kept as usize;
+        }
+        self.blocks
+            .into_iter()
+            .zip(keep)
+            .filter(|(_, kept)| *kept)
+            .map(|(mut block, _)| {
+                block.exit = match block.exit {
+                    Exit::Goto(to) => Exit::Goto(new_index[to]),
+                    Exit::Branch { cond, then, other } => {
+                        Exit::Branch { cond, then: new_index[then], other: new_index[other] }
+                    }
+                    exit => exit,
+                };
+                block
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::LexerConfig;
+    use crate::intern::Interner;
+    use crate::parser::parse;
+    use crate::source::FileId;
+
+    // each block as "stmt count -> exit"
+    fn shape(input: &str) -> Vec<String> {
+        let mut interner = Interner::new();
+        let (program, errors) = parse(input, FileId::default(), &LexerConfig::default(), &mut interner);
+        assert_eq!(errors, vec![]);
+        let cfg = &function_cfgs(&program)[0];
+        cfg.blocks
+            .iter()
+            .map(|block| {
+                let exit = match &block.exit {
+                    Exit::Goto(to) => format!("goto {}", to),
+                    Exit::Branch { cond, then, other } => {
+                        format!("if {} then {} else {}", cond.text(&interner), then, other)
+                    }
+                    Exit::Return(Some(value)) => format!("return {}", value.text(&interner)),
+                    Exit::Return(None) => "return".to_string(),
+                    Exit::End => "end".to_string(),
+                };
+                format!("{} -> {}", block.stmts.len(), exit)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn branches_and_loops_split_blocks() {
+        assert_eq!(
+            shape("func f(n : int32) [ let i = 0; while i < n [ if i % 2 == 0 [ print i; ] i += 1; ] return i; ]"),
+            vec![
+                "1 -> goto 1",
+                "0 -> if i < n then 2 else 5",
+                "0 -> if (i % 2) == 0 then 3 else 4",
+                "1 -> goto 4",
+                "1 -> goto 1",
+                "0 -> return i",
+            ]
+        );
+        assert_eq!(
+            shape("func f(x : bool) [ if x [ return; ] else [ print 1; ] print 2; ]"),
+            vec!["0 -> if x then 1 else 2", "0 -> return", "1 -> goto 3", "1 -> end"]
+        );
+    }
+}
@@ -0,0 +1,279 @@
+// Keyword and operator tables for the lexer, so course variants of the
+// language (`fn` vs `func`, `i32` vs `int32`, no XOR, ...) can share one lexer.
+use crate::Token;
+use crate::json::{self, Json};
+use std::collections::{HashMap, HashSet};
+use std::fs;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum OperatorFamily {
+    Arithmetic,        // + - * / %
+    CompoundAssign,    // += -= *= /=
+    Relational,        // == < > <= >=
+    NegatedRelational, // != !< !>
+    Logical,           // ! && ||
+    Xor,               // ^
+}
+
+impl OperatorFamily {
+    pub const ALL: [OperatorFamily; 6] = [
+        OperatorFamily::Arithmetic,
+        OperatorFamily::CompoundAssign,
+        OperatorFamily::Relational,
+        OperatorFamily::NegatedRelational,
+        OperatorFamily::Logical,
+        OperatorFamily::Xor,
+    ];
+
+    // the name used for the family in dialect files
+    pub fn name(self) -> &'static str {
+        match self {
+            OperatorFamily::Arithmetic => "arithmetic",
+            OperatorFamily::CompoundAssign => "compound_assign",
+            OperatorFamily::Relational => "relational",
+            OperatorFamily::NegatedRelational => "negated_relational",
+            OperatorFamily::Logical => "logical",
+            OperatorFamily::Xor => "xor",
+        }
+    }
+}
+
+// every token that is spelled as a word, with its spelling in the default dialect
+pub const DEFAULT_KEYWORDS: &[(&str, Token)] = &[
+    ("func", Token::FUNC),
+    ("let", Token::LET),
+    ("if", Token::IF),
+    ("else", Token::ELSE),
+    ("while", Token::WHILE),
+    ("print", Token::PRINT),
+    ("return", Token::RETURN),
+    ("struct", Token::STRUCT),
+    ("import", Token::IMPORT),
+    ("int32", Token::TYPE_INT32),
+    ("flt32", Token::TYPE_FLT32),
+    ("char", Token::TYPE_CHAR),
+    ("string", Token::TYPE_STRING),
+    ("bool", Token::TYPE_BOOL),
+    ("void", Token::TYPE_VOID),
+    ("true", Token::LIT_BOOL(true)),
+    ("false", Token::LIT_BOOL(false)),
+];
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct LexerConfig {
+    pub keywords: HashMap<String, Token>,
+    pub operators: HashSet<OperatorFamily>,
+}
+
+impl Default for LexerConfig {
+    fn default() -> Self {
+        LexerConfig {
+            keywords: DEFAULT_KEYWORDS.iter().map(|(s, t)| (s.to_string(), *t)).collect(),
+            operators: OperatorFamily::ALL.into_iter().collect(),
+        }
+    }
+}
+
+impl LexerConfig {
+    pub fn keyword(&self, word: &str) -> Option<&Token> {
+        self.keywords.get(word)
+    }
+
+    pub fn allows(&self, family: OperatorFamily) -> bool {
+        self.operators.contains(&family)
+    }
+
+    pub fn add_keyword(&mut self, spelling: &str, token: Token) -> Result<(), String> {
+        if !is_identifier(spelling) {
+            return Err(format!("'{}' can't be a keyword, it is not spelled like an identifier", spelling));
+        }
+        if !DEFAULT_KEYWORDS.iter().any(|(_, t)| *t == token) {
+            return Err(format!("{:?} is not a keyword token", token));
+        }
+        if let Some(existing) = self.keywords.get(spelling) {
+            return Err(format!("'{}' is already the keyword {:?}", spelling, existing));
+        }
+        self.keywords.insert(spelling.to_string(), token);
+        Ok(())
+    }
+
+    pub fn remove_keyword(&mut self, spelling: &str) -> Result<Token, String> {
+        self.keywords.remove(spelling).ok_or(format!("'{}' is not a keyword", spelling))
+    }
+
+    pub fn rename_keyword(&mut self, from: &str, to: &str) -> Result<(), String> {
+        let token = self.remove_keyword(from)?;
+        // put `from` back so a failed rename leaves the table as it was
+        self.add_keyword(to, token).inspect_err(|_| {
+            self.keywords.insert(from.to_string(), token);
+        })
+    }
+
+    pub fn load(path: &str) -> Result<LexerConfig, String> {
+        let text = fs::read_to_string(path).map_err(|e| format!("Error reading dialect {}: {}", path, e))?;
+        LexerConfig::from_json(&text).map_err(|e| format!("Error in dialect {}: {}", path, e))
+    }
+
+    // A dialect file starts from the default language and changes it, e.g.
+    // {
+    //   "keywords": { "rename": { "func": "fn", "int32": "i32" }, "remove": ["struct"],
+    //                 "add": { "fun": "FUNC" } },
+    //   "operators": { "xor": false, "compound_assign": false }
+    // }
+    // removals run first, then renames, then additions
+    pub fn from_json(text: &str) -> Result<LexerConfig, String> {
+        let dialect = json::parse(text)?;
+        let mut config = LexerConfig::default();
+        if !matches!(dialect, Json::Object(_)) {
+            return Err("A dialect must be a JSON object".to_string());
+        }
+
+        if let Some(keywords) = dialect.get("keywords") {
+            if let Some(remove) = keywords.get("remove") {
+                let Json::Array(words) = remove else {
+                    return Err("keywords.remove must be a list of keywords".to_string());
+                };
+                for word in words {
+                    config.remove_keyword(as_str(word, "keywords.remove")?)?;
+                }
+            }
+            for (from, to) in object(keywords.get("rename"), "keywords.rename")? {
+                config.rename_keyword(from, as_str(to, "keywords.rename")?)?;
+            }
+            for (spelling, name) in object(keywords.get("add"), "keywords.add")? {
+                let name = as_str(name, "keywords.add")?;
+                let token = DEFAULT_KEYWORDS
+                    .iter()
+                    .map(|(_, t)| t)
+                    .find(|t| format!("{:?}", t) == name)
+                    .ok_or(format!("Unknown keyword token {}", name))?;
+                config.add_keyword(spelling, *token)?;
+            }
+        }
+
+        for (name, enabled) in object(dialect.get("operators"), "operators")? {
+            let family = OperatorFamily::ALL
+                .into_iter()
+                .find(|f| f.name() == name)
+                .ok_or(format!("Unknown operator family {}", name))?;
+            match enabled {
+                Json::Bool(true) => config.operators.insert(family),
+                Json::Bool(false) => config.operators.remove(&family),
+                _ => return Err(format!("operators.{} must be true or false", name)),
+            };
+        }
+        Ok(config)
+    }
+}
+
+fn object<'a>(value: Option<&'a Json>, what: &str) -> Result<&'a [(String, Json)], String> {
+    match value {
+        None => Ok(&[]),
+        Some(Json::Object(fields)) => Ok(fields),
+        Some(_) => Err(format!("{} must be an object", what)),
+    }
+}
+
+fn as_str<'a>(value: &'a Json, what: &str) -> Result<&'a str, String> {
+    match value {
+        Json::Str(s) => Ok(s),
+        _ => Err(format!("{} entries must be strings", what)),
+    }
+}
+
+fn is_identifier(word: &str) -> bool {
+    let mut chars = word.chars();
+    match chars.next() {
+        Some(c) if c.is_ascii_alphabetic() || c == '_' => chars.all(|c| c.is_ascii_alphanumeric() || c == '_'),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Lexer;
+    use crate::intern::Interner;
+
+    fn lex(input: &str, config: &LexerConfig, interner: &mut Interner) -> Result<Vec<Token>, String> {
+        Lexer::tokenize_with_config(input, config, interner)
+            .map(|tokens| tokens.into_iter().map(|t| t.token).collect())
+            .map_err(|e| e.to_string())
+    }
+
+    #[test]
+    fn dialect_changes_keywords_and_operators() {
+        let config = LexerConfig::from_json(
+            r#"{
+                "keywords": {
+                    "remove": ["struct"],
+                    "rename": { "func": "fn", "int32": "i32" },
+                    "add": { "function": "FUNC", "yes": "LIT_BOOL(true)" }
+                },
+                "operators": { "xor": false, "compound_assign": false }
+            }"#,
+        )
+        .unwrap();
+        let mut interner = Interner::new();
+        let mut id = |s: &str| Token::ID(interner.intern(s));
+        let keywords = vec![
+            Token::FUNC,
+            Token::FUNC,
+            id("func"),
+            id("struct"),
+            Token::TYPE_INT32,
+            id("int32"),
+            Token::LIT_BOOL(true),
+            Token::EOI,
+        ];
+        // without compound assignment `+=` is two tokens
+        let compound = vec![id("a"), Token::ADD, Token::ASSIGN, Token::LIT_INT32(1), Token::EOI];
+        assert_eq!(lex("fn function func struct i32 int32 yes", &config, &mut interner), Ok(keywords));
+        assert_eq!(lex("a += 1", &config, &mut interner), Ok(compound));
+        assert_eq!(lex("a ^ b", &config, &mut interner), Err("Unexpected character: '^' at position 2".to_string()));
+    }
+
+    #[test]
+    fn disabled_relational_operators_are_unexpected() {
+        let config = LexerConfig::from_json(r#"{ "operators": { "relational": false } }"#).unwrap();
+        let mut interner = Interner::new();
+        assert_eq!(lex("a == b", &config, &mut interner), Err("Unexpected character: '=' at position 2".to_string()));
+        assert_eq!(lex("a < b", &config, &mut interner), Err("Unexpected character: '<' at position 2".to_string()));
+        assert!(lex("a = b", &config, &mut interner).is_ok());
+    }
+
+    #[test]
+    fn disabled_negated_relational_operators_are_unexpected() {
+        let config = LexerConfig::from_json(r#"{ "operators": { "negated_relational": false } }"#).unwrap();
+        let mut interner = Interner::new();
+        for input in ["a != b", "a !< b", "a !> b"] {
+            assert_eq!(lex(input, &config, &mut interner), Err("Unexpected character: '!' at position 2".to_string()));
+        }
+        assert!(lex("a = ! b", &config, &mut interner).is_ok());
+        assert!(lex("a = !(b < c)", &config, &mut interner).is_ok());
+    }
+
+    #[test]
+    fn bad_dialects_are_rejected() {
+        assert!(LexerConfig::from_json(r#"{ "keywords": { "remove": ["nope"] } }"#).is_err());
+        assert!(LexerConfig::from_json(r#"{ "keywords": { "add": { "fn": "ADD" } } }"#).is_err());
+        assert!(LexerConfig::from_json(r#"{ "keywords": { "rename": { "func": "f-n" } } }"#).is_err());
+        assert!(LexerConfig::from_json(r#"{ "keywords": { "rename": { "func": "let" } } }"#).is_err());
+        assert!(LexerConfig::from_json(r#"{ "keywords": { "add": { "if": "WHILE" } } }"#).is_err());
+        assert!(LexerConfig::from_json(r#"{ "operators": { "bitwise": false } }"#).is_err());
+        assert!(LexerConfig::from_json(r#"{ "operators": { "xor": 0 } }"#).is_err());
+        assert!(LexerConfig::from_json("[]").is_err());
+    }
+
+    #[test]
+    fn keywords_must_be_keyword_tokens_with_a_free_spelling() {
+        let mut config = LexerConfig::default();
+        assert!(config.add_keyword("x", Token::LIT_INT32(1)).is_err());
+        assert!(config.add_keyword("ident", Token::EOI).is_err());
+        assert!(config.add_keyword("else", Token::IF).is_err());
+        assert!(config.rename_keyword("func", "while").is_err());
+        assert_eq!(config, LexerConfig::default());
+        assert_eq!(config.add_keyword("fn", Token::FUNC), Ok(()));
+        assert_eq!(config.keyword("fn"), Some(&Token::FUNC));
+    }
+}
@@ -0,0 +1,530 @@
+// Graphviz output. lexer_graph draws the LexerState machine by running
+// Lexer::probe on sample characters from every state it can reach, so
+// the picture always matches the code. ast_graph and cfg_graph draw what the
+// parser made of a program.
+use crate::ast::*;
+use crate::cfg::{self, Exit};
+use crate::intern::Interner;
+use crate::{Lexer, LexerState, Token, unlex};
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
+
+// what one call to transition did
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+enum Outcome {
+    Goto(String),
+    Emit { token: String, consumed: bool },
+    Error,
+}
+
+// None stands for the end of the input
+type Probe = Option<char>;
+
+fn alphabet() -> Vec<Probe> {
+    let mut chars: Vec<Probe> = (0x20u8..0x7F).map(|b| Some(b as char)).collect();
+    chars.extend([Some('\t'), Some('\n'), Some('é'), None]);
+    chars
+}
+
+// text a state has already collected when it is entered
+fn seed_buffer(state: &LexerState) -> &'static str {
+    match state {
+        LexerState::InIdentifier => "a",
+        LexerState::InIntLit => "1",
+        LexerState::InFltLit => "1.5",
+        _ => "",
+    }
+}
+
+// escapes like \x41 need more than two chars, so the second char is followed
+// by each of these and the first one that is not an error counts
+const TAILS: &[&str] = &["", "'", "41'", "{41}'"];
+
+fn probe(state: &LexerState, first: Probe, second: Probe) -> Outcome {
+    let mut result = Outcome::Error;
+    for tail in TAILS {
+        let mut input: String = first.into_iter().collect();
+        if first.is_some() {
+            input.extend(second);
+            if second.is_some() {
+                input.push_str(tail);
+            }
+        }
+        result = match Lexer::probe(state.clone(), seed_buffer(state), &input) {
+            (Ok(None), next, _) => Outcome::Goto(format!("{:?}", next)),
+            (Ok(Some(token)), _, consumed) => Outcome::Emit { token: format!("{:?}", token.kind()), consumed: consumed > 0 },
+            (Err(_), _, _) => Outcome::Error,
+        };
+        if result != Outcome::Error || first.is_none() || second.is_none() {
+            break;
+        }
+    }
+    result
+}
+
+fn probe_label(c: Probe) -> String {
+    match c {
+        None => "EOI".to_string(),
+        Some(' ') => "space".to_string(),
+        Some('\t') => "\\t".to_string(),
+        Some('\n') => "\\n".to_string(),
+        Some(c) if !c.is_ascii() => "non-ASCII".to_string(),
+        Some(c) => c.to_string(),
+    }
+}
+
+// describes a set of probe chars compactly, e.g. [0-9 A-Z _ a-z] or [^=],
+// whichever of the set and its complement is shorter to write
+fn class_label(set: &BTreeSet<Probe>, all: &[Probe]) -> String {
+    if set.len() == all.len() {
+        return "any".to_string();
+    }
+    if set.len() == 1 {
+        return match set.first().unwrap() {
+            None => "EOI".to_string(),
+            Some(c) => format!("'{}'", probe_label(Some(*c))),
+        };
+    }
+    let members: Vec<Probe> = set.iter().copied().collect();
+    let others: Vec<Probe> = all.iter().filter(|c| !set.contains(c)).copied().collect();
+    let plain = format!("[{}]", ranges(&members));
+    let negated = format!("[^{}]", ranges(&others));
+    if negated.len() < plain.len() { negated } else { plain }
+}
+
+// collapses runs of four or more consecutive printable ASCII chars into a-b
+fn ranges(chars: &[Probe]) -> String {
+    let mut parts = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let mut j = i;
+        while let (Some(Some(a)), Some(Some(b))) = (chars.get(j), chars.get(j + 1)) {
+            if a.is_ascii_graphic() && b.is_ascii_graphic() && *b as u32 == *a as u32 + 1 {
+                j += 1;
+            } else {
+                break;
+            }
+        }
+        if j >= i + 3 {
+            parts.push(format!("{}-{}", probe_label(chars[i]), probe_label(chars[j])));
+        } else {
+            for c in &chars[i..=j] {
+                parts.push(probe_label(*c));
+            }
+        }
+        i = j + 1;
+    }
+    parts.join(" ")
+}
+
+fn escape(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct LexerGraph {
+    pub dot: String,
+    pub unreachable: Vec<LexerState>,
+}
+
+pub fn lexer_graph() -> LexerGraph {
+    let all = alphabet();
+    let mut edges: BTreeSet<(String, String, Outcome)> = BTreeSet::new();
+
+    let mut seen = vec![LexerState::Start];
+    let mut queue = VecDeque::from([LexerState::Start]);
+    while let Some(state) = queue.pop_front() {
+        let name = format!("{:?}", state);
+
+        // outcome of each first char, split by second char where that matters
+        let mut by_first: BTreeMap<Probe, Vec<(Probe, Outcome)>> = BTreeMap::new();
+        for &first in &all {
+            let mut outcomes = Vec::new();
+            for &second in &all {
+                outcomes.push((second, probe(&state, first, second)));
+                if first.is_none() {
+                    break;
+                }
+            }
+            let distinct: BTreeSet<&Outcome> = outcomes.iter().map(|(_, o)| o).collect();
+            if distinct.len() == 1 {
+                outcomes.truncate(1);
+                outcomes[0].0 = None;
+            }
+            by_first.insert(first, outcomes);
+        }
+
+        // first chars that behave the same are drawn as one class
+        let mut classes: BTreeMap<Vec<(Probe, Outcome)>, BTreeSet<Probe>> = BTreeMap::new();
+        for (first, outcomes) in by_first {
+            classes.entry(outcomes).or_default().insert(first);
+        }
+        for (outcomes, firsts) in classes {
+            let first_label = class_label(&firsts, &all);
+            if outcomes.len() == 1 {
+                edges.insert((name.clone(), first_label, outcomes[0].1.clone()));
+                continue;
+            }
+            let mut by_outcome: BTreeMap<Outcome, BTreeSet<Probe>> = BTreeMap::new();
+            for (second, outcome) in outcomes {
+                by_outcome.entry(outcome).or_default().insert(second);
+            }
+            for (outcome, seconds) in by_outcome {
+                let label = format!("{} {}", first_label, class_label(&seconds, &all));
+                edges.insert((name.clone(), label, outcome));
+            }
+        }
+
+        for (_, _, outcome) in edges.iter().filter(|(from, _, _)| *from == name) {
+            let Outcome::Goto(target) = outcome else { continue };
+            if let Some(next) = LexerState::ALL.iter().find(|s| format!("{:?}", s) == *target)
+                && !seen.contains(next)
+            {
+                seen.push(next.clone());
+                queue.push_back(next.clone());
+            }
+        }
+    }
+
+    let unreachable: Vec<LexerState> = LexerState::ALL.iter().filter(|s| !seen.contains(s)).cloned().collect();
+
+    let mut dot = String::new();
+    dot.push_str("digraph Lexer {\n");
+    dot.push_str("    rankdir=LR;\n");
+    dot.push_str("    node [shape=circle];\n");
+    dot.push_str("    start [shape=point];\n");
+    dot.push_str("    start -> Start;\n");
+    for state in &seen {
+        dot.push_str(&format!("    {:?};\n", state));
+    }
+    for state in &unreachable {
+        dot.push_str(&format!(
+            "    {:?} [style=dashed, color=gray, fontcolor=gray, label=\"{:?}\\n(unreachable)\"];\n",
+            state, state
+        ));
+    }
+    let tokens: BTreeSet<&String> = edges
+        .iter()
+        .filter_map(|(_, _, o)| match o {
+            Outcome::Emit { token, .. } => Some(token),
+            _ => None,
+        })
+        .collect();
+    for token in tokens {
+        dot.push_str(&format!("    tok_{} [shape=box, label=\"{}\"];\n", token, token));
+    }
+    dot.push_str("    error [shape=octagon, color=red];\n");
+    for (from, label, outcome) in &edges {
+        let (target, label) = match outcome {
+            Outcome::Goto(state) => (state.clone(), label.clone()),
+            Outcome::Emit { token, consumed: true } => (format!("tok_{}", token), label.clone()),
+            // the char only ended the token, the next token starts with it
+            Outcome::Emit { token, consumed: false } => (format!("tok_{}", token), format!("{} (peek)", label)),
+            Outcome::Error => ("error".to_string(), label.clone()),
+        };
+        dot.push_str(&format!("    {} -> {} [label=\"{}\"];\n", from, target, escape(&label)));
+    }
+    dot.push_str("}\n");
+
+    LexerGraph { dot, unreachable }
+}
+
+// a DOT label, where '\n' starts a new centred line
+fn label(text: &str) -> String {
+    escape(text).replace('\n', "\\n")
+}
+
+// ---------------------------------------------------------------------------
+// syntax trees
+
+struct AstGraph<'a> {
+    dot: String,
+    nodes: usize,
+    interner: &'a Interner,
+}
+
+impl AstGraph<'_> {
+    fn node(&mut self, text: &str) -> usize {
+        self.nodes += 1;
+        self.dot.push_str(&format!("    n{} [label=\"{}\"];\n", self.nodes, label(text)));
+        self.nodes
+    }
+
+    fn edge(&mut self, from: usize, to: usize, name: &str) {
+        self.dot.push_str(&format!("    n{} -> n{} [label=\"{}\"];\n", from, to, label(name)));
+    }
+
+    fn item(&mut self, item: &Item) -> usize {
+        match &item.kind {
+            ItemKind::Func { name, params, ret, body } => {
+                let ret = ret.as_ref().map_or(String::new(), |ty| format!(" -> {}", ty.text(self.interner)));
+                let id = self.node(&format!("Func\n{}{}", self.interner.get(*name), ret));
+                for param in params {
+                    let child = self.param(param);
+                    self.edge(id, child, "param");
+                }
+                let child = self.block(body);
+                self.edge(id, child, "body");
+                id
+            }
+            ItemKind::Struct { name, fields } => {
+                let id = self.node(&format!("Struct\n{}", self.interner.get(*name)));
+                for field in fields {
+                    let child = self.param(field);
+                    self.edge(id, child, "field");
+                }
+                id
+            }
+            ItemKind::Import(path) => {
+                self.node(&format!("Import\n{}", unlex::spelling(&Token::LIT_STRING(*path), self.interner)))
+            }
+            ItemKind::Global(stmt) => {
+                let id = self.node("Global");
+                let child = self.stmt(stmt);
+                self.edge(id, child, "");
+                id
+            }
+        }
+    }
+
+    fn param(&mut self, param: &Param) -> usize {
+        self.node(&format!("Param\n{} : {}", self.interner.get(param.name), param.ty.text(self.interner)))
+    }
+
+    fn block(&mut self, block: &Block) -> usize {
+        let id = self.node("Block");
+        for (i, stmt) in block.stmts.iter().enumerate() {
+            let child = self.stmt(stmt);
+            self.edge(id, child, &i.to_string());
+        }
+        id
+    }
+
+    fn child_expr(&mut self, parent: usize, expr: &Expr, name: &str) {
+        let child = self.expr(expr);
+        self.edge(parent, child, name);
+    }
+
+    fn child_block(&mut self, parent: usize, block: &Block, name: &str) {
+        let child = self.block(block);
+        self.edge(parent, child, name);
+    }
+
+    fn stmt(&mut self, stmt: &Stmt) -> usize {
+        match &stmt.kind {
+            StmtKind::Let { name, ty, value } => {
+                let ty = ty.as_ref().map_or(String::new(), |ty| format!(" : {}", ty.text(self.interner)));
+                let id = self.node(&format!("Let\n{}{}", self.interner.get(*name), ty));
+                if let Some(value) = value {
+                    self.child_expr(id, value, "value");
+                }
+                id
+            }
+            StmtKind::Assign { target, op, value } => {
+                let id = self.node(&format!("Assign\n{:?}", op.kind()));
+                self.child_expr(id, target, "target");
+                self.child_expr(id, value, "value");
+                id
+            }
+            StmtKind::If { cond, then_block, else_block } => {
+                let id = self.node("If");
+                self.child_expr(id, cond, "cond");
+                self.child_block(id, then_block, "then");
+                if let Some(else_block) = else_block {
+                    self.child_block(id, else_block, "else");
+                }
+                id
+            }
+            StmtKind::While { cond, body } => {
+                let id = self.node("While");
+                self.child_expr(id, cond, "cond");
+                self.child_block(id, body, "body");
+                id
+            }
+            StmtKind::Return(value) => {
+                let id = self.node("Return");
+                if let Some(value) = value {
+                    self.child_expr(id, value, "value");
+                }
+                id
+            }
+            StmtKind::Print(args) => {
+                let id = self.node("Print");
+                for (i, arg) in args.iter().enumerate() {
+                    self.child_expr(id, arg, &i.to_string());
+                }
+                id
+            }
+            StmtKind::Block(block) => self.block(block),
+            StmtKind::Expr(expr) => self.expr(expr),
+        }
+    }
+
+    // literals and names are labelled with the kind of token they came from
+    fn expr(&mut self, expr: &Expr) -> usize {
+        match &expr.kind {
+            ExprKind::Int(_) => self.node(&format!("LIT_INT32\n{}", expr.text(self.interner))),
+            ExprKind::Float(_) => self.node(&format!("LIT_FLT32\n{}", expr.text(self.interner))),
+            ExprKind::Char(_) => self.node(&format!("LIT_CHAR\n{}", expr.text(self.interner))),
+            ExprKind::Str(_) => self.node(&format!("LIT_STRING\n{}", expr.text(self.interner))),
+            ExprKind::Bool(_) => self.node(&format!("LIT_BOOL\n{}", expr.text(self.interner))),
+            ExprKind::Var(name) => self.node(&format!("ID\n{}", self.interner.get(*name))),
+            ExprKind::Unary { op, operand } => {
+                let id = self.node(&format!("Unary\n{:?}", op.kind()));
+                self.child_expr(id, operand, "");
+                id
+            }
+            ExprKind::Binary { op, left, right } => {
+                let id = self.node(&format!("Binary\n{:?}", op.kind()));
+                self.child_expr(id, left, "left");
+                self.child_expr(id, right, "right");
+                id
+            }
+            ExprKind::Call { callee, args } => {
+                let id = self.node("Call");
+                self.child_expr(id, callee, "callee");
+                for (i, arg) in args.iter().enumerate() {
+                    self.child_expr(id, arg, &i.to_string());
+                }
+                id
+            }
+            ExprKind::Field { object, field } => {
+                let id = self.node(&format!("Field\n{}", self.interner.get(*field)));
+                self.child_expr(id, object, "");
+                id
+            }
+        }
+    }
+}
+
+pub fn ast_graph(program: &Program, interner: &Interner) -> String {
+    let mut graph = AstGraph { dot: String::new(), nodes: 0, interner };
+    graph.dot.push_str("digraph AST {\n");
+    graph.dot.push_str("    node [shape=box];\n");
+    let root = graph.node("Program");
+    for item in &program.items {
+        let child = graph.item(item);
+        graph.edge(root, child, "");
+    }
+    graph.dot.push_str("}\n");
+    graph.dot
+}
+
+// ---------------------------------------------------------------------------
+// control-flow graphs
+
+// the one-line source form of a statement kept in a basic block
+fn stmt_text(stmt: &Stmt, interner: &Interner) -> String {
+    match &stmt.kind {
+        StmtKind::Let { name, ty, value } => {
+            let ty = ty.as_ref().map_or(String::new(), |ty| format!(" : {}", ty.text(interner)));
+            let value = value.as_ref().map_or(String::new(), |value| format!(" = {}", value.text(interner)));
+            format!("let {}{}{}", interner.get(*name), ty, value)
+        }
+        StmtKind::Assign { target, op, value } => {
+            format!("{} {} {}", target.text(interner), unlex::spelling(op, interner), value.text(interner))
+        }
+        StmtKind::Print(args) => {
+            format!("print {}", args.iter().map(|a| a.text(interner)).collect::<Vec<_>>().join(", "))
+        }
+        StmtKind::Expr(expr) => expr.text(interner),
+        StmtKind::If { cond, .. } => format!("if {}", cond.text(interner)),
+        StmtKind::While { cond, .. } => format!("while {}", cond.text(interner)),
+        StmtKind::Return(_) => "return".to_string(),
+        StmtKind::Block(_) => "[ ... ]".to_string(),
+    }
+}
+
+// one cluster per function. Blocks list their statements and end with the
+// condition they branch on; `return` and the end of the function lead to exit
+pub fn cfg_graph(program: &Program, interner: &Interner) -> String {
+    let mut dot = String::new();
+    dot.push_str("digraph CFG {\n");
+    dot.push_str("    node [shape=box, fontname=monospace];\n");
+    for (f, function) in cfg::function_cfgs(program).iter().enumerate() {
+        dot.push_str(&format!("    subgraph cluster_{} {{\n", f));
+        dot.push_str(&format!("        label=\"func {}\";\n", escape(interner.get(function.name))));
+        dot.push_str(&format!("        f{}_exit [shape=oval, label=\"exit\"];\n", f));
+        for (b, block) in function.blocks.iter().enumerate() {
+            let mut lines = vec![if b == 0 { "B0 (entry)".to_string() } else { format!("B{}", b) }];
+            lines.extend(block.stmts.iter().map(|stmt| stmt_text(stmt, interner)));
+            match &block.exit {
+                Exit::Branch { cond, .. } => lines.push(format!("if {}", cond.text(interner))),
+                Exit::Return(Some(value)) => lines.push(format!("return {}", value.text(interner))),
+                Exit::Return(None) => lines.push("return".to_string()),
+                _ => {}
+            }
+            let text: String = lines.iter().map(|line| format!("{}\\l", escape(line))).collect();
+            dot.push_str(&format!("        f{}_b{} [label=\"{}\"];\n", f, b, text));
+        }
+        for (b, block) in function.blocks.iter().enumerate() {
+            match &block.exit {
+                Exit::Goto(to) => dot.push_str(&format!("        f{}_b{} -> f{}_b{};\n", f, b, f, to)),
+                Exit::Branch { then, other, .. } => {
+                    dot.push_str(&format!("        f{}_b{} -> f{}_b{} [label=\"true\"];\n", f, b, f, then));
+                    dot.push_str(&format!("        f{}_b{} -> f{}_b{} [label=\"false\"];\n", f, b, f, other));
+                }
+                Exit::Return(_) | Exit::End => dot.push_str(&format!("        f{}_b{} -> f{}_exit;\n", f, b, f)),
+            }
+        }
+        dot.push_str("    }\n");
+    }
+    dot.push_str("}\n");
+    dot
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lexer_graph_flags_unused_states() {
+        let graph = lexer_graph();
+        for state in [LexerState::InFunc, LexerState::InLet, LexerState::InCharType, LexerState::InEOI] {
+            assert!(graph.unreachable.contains(&state), "{:?} should be unreachable", state);
+        }
+        for state in [LexerState::InIdentifier, LexerState::InString, LexerState::InLessThan] {
+            assert!(!graph.unreachable.contains(&state));
+        }
+        assert!(graph.dot.contains("Start -> InIntLit [label=\"[0-9]\"];"));
+        assert!(graph.dot.contains("InLessThan -> tok_LE [label=\"'='\"];"));
+        assert!(graph.dot.contains("InIdentifier -> tok_ID [label=\"[^0-9 A-Z _ a-z] (peek)\"];"));
+        assert!(graph.dot.contains("Start -> tok_EQ [label=\"'=' '='\"];"));
+        assert!(graph.dot.contains("InIntLit -> InFltLit [label=\"'.' [0-9]\"];"));
+    }
+
+    #[test]
+    fn ast_and_cfg_graphs() {
+        let source = "func f(n : int32) -> int32 [ while n > 0 [ n -= 1; ] print \"done\"; return n * 2; ]";
+        let mut interner = Interner::new();
+        let (program, errors) = crate::parser::parse(source, Default::default(), &Default::default(), &mut interner);
+        assert_eq!(errors, vec![]);
+
+        let ast = ast_graph(&program, &interner);
+        for line in [
+            "n2 [label=\"Func\\nf -> int32\"];",
+            "n3 [label=\"Param\\nn : int32\"];",
+            "n10 [label=\"Assign\\nSUB_ASSIGN\"];",
+            "n12 [label=\"LIT_INT32\\n1\"];",
+            "n13 [label=\"Print\"];",
+            "n14 [label=\"LIT_STRING\\n\\\"done\\\"\"];",
+            "n16 [label=\"Binary\\nMUL\"];",
+            "n5 -> n6 [label=\"cond\"];",
+        ] {
+            assert!(ast.contains(line), "missing {} in\n{}", line, ast);
+        }
+
+        let cfg = cfg_graph(&program, &interner);
+        for line in [
+            "label=\"func f\";",
+            "f0_b1 [label=\"B1\\lif n > 0\\l\"];",
+            "f0_b2 [label=\"B2\\ln -= 1\\l\"];",
+            "f0_b3 [label=\"B3\\lprint \\\"done\\\"\\lreturn n * 2\\l\"];",
+            "f0_b1 -> f0_b2 [label=\"true\"];",
+            "f0_b1 -> f0_b3 [label=\"false\"];",
+            "f0_b2 -> f0_b1;",
+            "f0_b3 -> f0_exit;",
+        ] {
+            assert!(cfg.contains(line), "missing {} in\n{}", line, cfg);
+        }
+    }
+}
@@ -0,0 +1,641 @@
+// AST dumps for tools and golden tests. JSON can be read back with
+// program_from_json, S-expressions are for reading by eye. Both carry every
+// node's kind and span ([start, end) byte offsets) and literal values.
+use crate::ast::*;
+use crate::intern::{Interner, Symbol};
+use crate::json::{self, Json};
+use crate::parser::{ASSIGN_OPERATORS, BINARY_OPERATORS, UNARY_OPERATORS};
+use crate::source::FileId;
+use crate::{Span, Token, TokenKind, TokenSet, unlex};
+
+// ---------------------------------------------------------------------------
+// JSON
+
+pub fn program_to_json(program: &Program, interner: &Interner) -> Json {
+    Json::Object(vec![
+        ("kind".to_string(), Json::Str("Program".to_string())),
+        ("items".to_string(), Json::Array(program.items.iter().map(|item| item_json(item, interner)).collect())),
+    ])
+}
+
+fn node(kind: &str, span: Span, fields: Vec<(&str, Json)>) -> Json {
+    let mut all = vec![
+        ("kind".to_string(), Json::Str(kind.to_string())),
+        ("span".to_string(), Json::Array(vec![Json::Number(span.start as f64), Json::Number(span.end as f64)])),
+    ];
+    all.extend(fields.into_iter().map(|(key, value)| (key.to_string(), value)));
+    Json::Object(all)
+}
+
+// JSON has no infinity, which is what a literal too big for f32 lexes to, so
+// infinite values are written as the strings "inf" and "-inf"
+fn float_json(value: f32) -> Json {
+    if value.is_finite() { Json::Number(value as f64) } else { Json::Str(value.to_string()) }
+}
+
+fn str_json(s: &str) -> Json {
+    Json::Str(s.to_string())
+}
+
+fn type_json(ty: &Type, interner: &Interner) -> Json {
+    match ty {
+        Type::Named(name) => Json::Object(vec![("struct".to_string(), str_json(interner.get(*name)))]),
+        _ => str_json(ty.text(interner)),
+    }
+}
+
+fn or_null<T>(value: Option<&T>, to_json: impl Fn(&T) -> Json) -> Json {
+    value.map_or(Json::Null, to_json)
+}
+
+fn item_json(item: &Item, interner: &Interner) -> Json {
+    match &item.kind {
+        ItemKind::Func { name, params, ret, body } => node(
+            "Func",
+            item.span,
+            vec![
+                ("name", str_json(interner.get(*name))),
+                ("params", Json::Array(params.iter().map(|param| param_json(param, interner)).collect())),
+                ("ret", or_null(ret.as_ref(), |ty| type_json(ty, interner))),
+                ("body", block_json(body, interner)),
+            ],
+        ),
+        ItemKind::Struct { name, fields } => node(
+            "Struct",
+            item.span,
+            vec![
+                ("name", str_json(interner.get(*name))),
+                ("fields", Json::Array(fields.iter().map(|param| param_json(param, interner)).collect())),
+            ],
+        ),
+        ItemKind::Import(path) => node("Import", item.span, vec![("path", str_json(interner.get(*path)))]),
+        ItemKind::Global(stmt) => node("Global", item.span, vec![("stmt", stmt_json(stmt, interner))]),
+    }
+}
+
+fn param_json(param: &Param, interner: &Interner) -> Json {
+    node("Param", param.span, vec![("name", str_json(interner.get(param.name))), ("type", type_json(&param.ty, interner))])
+}
+
+fn block_json(block: &Block, interner: &Interner) -> Json {
+    node("Block", block.span, vec![("stmts", Json::Array(block.stmts.iter().map(|stmt| stmt_json(stmt, interner)).collect()))])
+}
+
+fn stmt_json(stmt: &Stmt, interner: &Interner) -> Json {
+    let span = stmt.span;
+    match &stmt.kind {
+        StmtKind::Let { name, ty, value } => node(
+            "Let",
+            span,
+            vec![
+                ("name", str_json(interner.get(*name))),
+                ("type", or_null(ty.as_ref(), |ty| type_json(ty, interner))),
+                ("value", or_null(value.as_ref(), |expr| expr_json(expr, interner))),
+            ],
+        ),
+        StmtKind::Assign { target, op, value } => node(
+            "Assign",
+            span,
+            vec![
+                ("op", str_json(&unlex::spelling(op, interner))),
+                ("target", expr_json(target, interner)),
+                ("value", expr_json(value, interner)),
+            ],
+        ),
+        StmtKind::If { cond, then_block, else_block } => node(
+            "If",
+            span,
+            vec![
+                ("cond", expr_json(cond, interner)),
+                ("then", block_json(then_block, interner)),
+                ("else", or_null(else_block.as_ref(), |block| block_json(block, interner))),
+            ],
+        ),
+        StmtKind::While { cond, body } => {
+            node("While", span, vec![("cond", expr_json(cond, interner)), ("body", block_json(body, interner))])
+        }
+        StmtKind::Return(value) => node("Return", span, vec![("value", or_null(value.as_ref(), |expr| expr_json(expr, interner)))]),
+        StmtKind::Print(args) => {
+            node("Print", span, vec![("args", Json::Array(args.iter().map(|expr| expr_json(expr, interner)).collect()))])
+        }
+        // a block statement spans exactly its block
+        StmtKind::Block(block) => block_json(block, interner),
+        StmtKind::Expr(expr) => node("ExprStmt", span, vec![("expr", expr_json(expr, interner))]),
+    }
+}
+
+fn expr_json(expr: &Expr, interner: &Interner) -> Json {
+    let span = expr.span;
+    match &expr.kind {
+        ExprKind::Int(value) => node("Int", span, vec![("value", Json::Number(*value as f64))]),
+        ExprKind::Float(value) => node("Float", span, vec![("value", float_json(*value))]),
+        ExprKind::Char(value) => node("Char", span, vec![("value", Json::Str(value.to_string()))]),
+        ExprKind::Str(value) => node("Str", span, vec![("value", str_json(interner.get(*value)))]),
+        ExprKind::Bool(value) => node("Bool", span, vec![("value", Json::Bool(*value))]),
+        ExprKind::Var(name) => node("Var", span, vec![("name", str_json(interner.get(*name)))]),
+        ExprKind::Unary { op, operand } => {
+            node("Unary", span, vec![("op", str_json(&unlex::spelling(op, interner))), ("operand", expr_json(operand, interner))])
+        }
+        ExprKind::Binary { op, left, right } => node(
+            "Binary",
+            span,
+            vec![
+                ("op", str_json(&unlex::spelling(op, interner))),
+                ("left", expr_json(left, interner)),
+                ("right", expr_json(right, interner)),
+            ],
+        ),
+        ExprKind::Call { callee, args } => node(
+            "Call",
+            span,
+            vec![
+                ("callee", expr_json(callee, interner)),
+                ("args", Json::Array(args.iter().map(|expr| expr_json(expr, interner)).collect())),
+            ],
+        ),
+        ExprKind::Field { object, field } => {
+            node("Field", span, vec![("object", expr_json(object, interner)), ("field", str_json(interner.get(*field)))])
+        }
+    }
+}
+
+// Reads an AST written by program_to_json, interning its names into
+// `interner`. Spans point into FileId 0; errors name the path of the bad
+// node, e.g. items[0].body.stmts[2].value
+pub fn program_from_json(text: &str, interner: &mut Interner) -> Result<Program, String> {
+    let value = json::parse(text)?;
+    expect_kind(&value, &["Program"], "program")?;
+    let items = array(&value, "items", "program")?
+        .iter()
+        .enumerate()
+        .map(|(i, item)| item_from(item, &format!("items[{}]", i), interner))
+        .collect::<Result<_, _>>()?;
+    Ok(Program { items })
+}
+
+fn field<'a>(value: &'a Json, key: &str, path: &str) -> Result<&'a Json, String> {
+    value.get(key).ok_or(format!("{}: missing \"{}\"", path, key))
+}
+
+// a field that may be null or left out
+fn optional<'a>(value: &'a Json, key: &str) -> Option<&'a Json> {
+    value.get(key).filter(|v| **v != Json::Null)
+}
+
+fn text<'a>(value: &'a Json, key: &str, path: &str) -> Result<&'a str, String> {
+    match field(value, key, path)? {
+        Json::Str(s) => Ok(s),
+        _ => Err(format!("{}.{}: expected a string", path, key)),
+    }
+}
+
+fn string(value: &Json, key: &str, path: &str, interner: &mut Interner) -> Result<Symbol, String> {
+    Ok(interner.intern(text(value, key, path)?))
+}
+
+fn array<'a>(value: &'a Json, key: &str, path: &str) -> Result<&'a [Json], String> {
+    match field(value, key, path)? {
+        Json::Array(items) => Ok(items),
+        _ => Err(format!("{}.{}: expected an array", path, key)),
+    }
+}
+
+fn read_list<T>(
+    value: &Json,
+    key: &str,
+    path: &str,
+    read: fn(&Json, &str, &mut Interner) -> Result<T, String>,
+    interner: &mut Interner,
+) -> Result<Vec<T>, String> {
+    array(value, key, path)?
+        .iter()
+        .enumerate()
+        .map(|(i, v)| read(v, &format!("{}.{}[{}]", path, key, i), interner))
+        .collect()
+}
+
+fn expect_kind<'a>(value: &'a Json, kinds: &[&str], path: &str) -> Result<&'a str, String> {
+    match field(value, "kind", path)? {
+        Json::Str(kind) if kinds.contains(&kind.as_str()) => Ok(kind),
+        Json::Str(kind) => Err(format!("{}: unexpected node kind {}, expected {}", path, kind, kinds.join(", "))),
+        _ => Err(format!("{}.kind: expected a string", path)),
+    }
+}
+
+fn span_from(value: &Json, path: &str) -> Result<Span, String> {
+    let bad = || format!("{}.span: expected [start, end] byte offsets", path);
+    let Json::Array(bounds) = field(value, "span", path)? else { return Err(bad()) };
+    let offsets: Vec<usize> = bounds
+        .iter()
+        .map(|b| match b {
+            Json::Number(n) if *n >= 0.0 && n.fract() == 0.0 => Some(*n as usize),
+            _ => None,
+        })
+        .collect::<Option<_>>()
+        .ok_or_else(bad)?;
+    match offsets[..] {
+        [start, end] if start <= end => Ok(Span { file: FileId::default(), start, end }),
+        _ => Err(bad()),
+    }
+}
+
+fn type_from(value: &Json, path: &str, interner: &mut Interner) -> Result<Type, String> {
+    let ty = match value {
+        Json::Str(name) => match name.as_str() {
+            "int32" => Type::Int32,
+            "flt32" => Type::Flt32,
+            "char" => Type::Char,
+            "string" => Type::String,
+            "bool" => Type::Bool,
+            "void" => Type::Void,
+            _ => return Err(format!("{}: unknown type {}", path, name)),
+        },
+        Json::Object(_) => Type::Named(string(value, "struct", path, interner)?),
+        _ => return Err(format!("{}: expected a type", path)),
+    };
+    Ok(ty)
+}
+
+// the operator token spelled `key`, which must be one of `allowed`
+fn operator(value: &Json, key: &str, path: &str, allowed: TokenSet, interner: &Interner) -> Result<Token, String> {
+    let spelling = text(value, key, path)?;
+    allowed
+        .iter()
+        .filter_map(TokenKind::token)
+        .find(|token| unlex::spelling(token, interner) == spelling)
+        .ok_or(format!("{}.{}: {} is not an operator here", path, key, spelling))
+}
+
+fn item_from(value: &Json, path: &str, interner: &mut Interner) -> Result<Item, String> {
+    let kind = match expect_kind(value, &["Func", "Struct", "Import", "Global"], path)? {
+        "Func" => ItemKind::Func {
+            name: string(value, "name", path, interner)?,
+            params: read_list(value, "params", path, param_from, interner)?,
+            ret: optional(value, "ret").map(|ret| type_from(ret, &format!("{}.ret", path), interner)).transpose()?,
+            body: block_from(field(value, "body", path)?, &format!("{}.body", path), interner)?,
+        },
+        "Struct" => ItemKind::Struct {
+            name: string(value, "name", path, interner)?,
+            fields: read_list(value, "fields", path, param_from, interner)?,
+        },
+        "Import" => ItemKind::Import(string(value, "path", path, interner)?),
+        _ => ItemKind::Global(stmt_from(field(value, "stmt", path)?, &format!("{}.stmt", path), interner)?),
+    };
+    Ok(Item { kind, span: span_from(value, path)? })
+}
+
+fn param_from(value: &Json, path: &str, interner: &mut Interner) -> Result<Param, String> {
+    expect_kind(value, &["Param"], path)?;
+    Ok(Param {
+        name: string(value, "name", path, interner)?,
+        ty: type_from(field(value, "type", path)?, &format!("{}.type", path), interner)?,
+        span: span_from(value, path)?,
+    })
+}
+
+fn block_from(value: &Json, path: &str, interner: &mut Interner) -> Result<Block, String> {
+    expect_kind(value, &["Block"], path)?;
+    Ok(Block { stmts: read_list(value, "stmts", path, stmt_from, interner)?, span: span_from(value, path)? })
+}
+
+fn expr_field(value: &Json, key: &str, path: &str, interner: &mut Interner) -> Result<Expr, String> {
+    expr_from(field(value, key, path)?, &format!("{}.{}", path, key), interner)
+}
+
+fn block_field(value: &Json, key: &str, path: &str, interner: &mut Interner) -> Result<Block, String> {
+    block_from(field(value, key, path)?, &format!("{}.{}", path, key), interner)
+}
+
+fn stmt_from(value: &Json, path: &str, interner: &mut Interner) -> Result<Stmt, String> {
+    let kinds = ["Let", "Assign", "If", "While", "Return", "Print", "Block", "ExprStmt"];
+    let kind = match expect_kind(value, &kinds, path)? {
+        "Let" => StmtKind::Let {
+            name: string(value, "name", path, interner)?,
+            ty: optional(value, "type").map(|ty| type_from(ty, &format!("{}.type", path), interner)).transpose()?,
+            value: optional(value, "value").map(|_| expr_field(value, "value", path, interner)).transpose()?,
+        },
+        "Assign" => StmtKind::Assign {
+            target: expr_field(value, "target", path, interner)?,
+            op: operator(value, "op", path, ASSIGN_OPERATORS, interner)?,
+            value: expr_field(value, "value", path, interner)?,
+        },
+        "If" => StmtKind::If {
+            cond: expr_field(value, "cond", path, interner)?,
+            then_block: block_field(value, "then", path, interner)?,
+            else_block: optional(value, "else").map(|_| block_field(value, "else", path, interner)).transpose()?,
+        },
+        "While" => StmtKind::While {
+            cond: expr_field(value, "cond", path, interner)?,
+            body: block_field(value, "body", path, interner)?,
+        },
+        "Return" => StmtKind::Return(optional(value, "value").map(|_| expr_field(value, "value", path, interner)).transpose()?),
+        "Print" => StmtKind::Print(read_list(value, "args", path, expr_from, interner)?),
+        "Block" => StmtKind::Block(block_from(value, path, interner)?),
+        _ => StmtKind::Expr(expr_field(value, "expr", path, interner)?),
+    };
+    Ok(Stmt { kind, span: span_from(value, path)? })
+}
+
+fn expr_from(value: &Json, path: &str, interner: &mut Interner) -> Result<Expr, String> {
+    let kinds = ["Int", "Float", "Char", "Str", "Bool", "Var", "Unary", "Binary", "Call", "Field"];
+    let literal = || field(value, "value", path);
+    let bad_value = |what: &str| format!("{}.value: expected {}", path, what);
+    let kind = match expect_kind(value, &kinds, path)? {
+        "Int" => match literal()? {
+            Json::Number(n) if n.fract() == 0.0 && (i32::MIN as f64..=i32::MAX as f64).contains(n) => {
+                ExprKind::Int(*n as i32)
+            }
+            _ => return Err(bad_value("an int32")),
+        },
+        "Float" => match literal()? {
+            Json::Number(n) => ExprKind::Float(*n as f32),
+            // no literal lexes to NaN, so it can't be in a tree either
+            Json::Str(s) if matches!(s.as_str(), "inf" | "-inf") => ExprKind::Float(s.parse().unwrap()),
+            _ => return Err(bad_value("a number, \"inf\" or \"-inf\"")),
+        },
+        "Char" => match literal()? {
+            Json::Str(s) if s.chars().count() == 1 => ExprKind::Char(s.chars().next().unwrap()),
+            _ => return Err(bad_value("a one-character string")),
+        },
+        "Str" => ExprKind::Str(string(value, "value", path, interner)?),
+        "Bool" => match literal()? {
+            Json::Bool(b) => ExprKind::Bool(*b),
+            _ => return Err(bad_value("true or false")),
+        },
+        "Var" => ExprKind::Var(string(value, "name", path, interner)?),
+        "Unary" => ExprKind::Unary {
+            op: operator(value, "op", path, UNARY_OPERATORS, interner)?,
+            operand: Box::new(expr_field(value, "operand", path, interner)?),
+        },
+        "Binary" => ExprKind::Binary {
+            op: operator(value, "op", path, BINARY_OPERATORS, interner)?,
+            left: Box::new(expr_field(value, "left", path, interner)?),
+            right: Box::new(expr_field(value, "right", path, interner)?),
+        },
+        "Call" => ExprKind::Call {
+            callee: Box::new(expr_field(value, "callee", path, interner)?),
+            args: read_list(value, "args", path, expr_from, interner)?,
+        },
+        _ => ExprKind::Field {
+            object: Box::new(expr_field(value, "object", path, interner)?),
+            field: string(value, "field", path, interner)?,
+        },
+    };
+    Ok(Expr { kind, span: span_from(value, path)? })
+}
+
+// ---------------------------------------------------------------------------
+// S-expressions
+
+// a node is (kind@start..end values...), missing parts are written as _
+enum Sexp {
+    Atom(String),
+    List(Vec<Sexp>),
+}
+
+const SEXP_WIDTH: usize = 80;
+
+pub fn program_to_sexp(program: &Program, interner: &Interner) -> String {
+    let mut parts = vec![atom("program")];
+    parts.extend(program.items.iter().map(|item| item_sexp(item, interner)));
+    let mut out = String::new();
+    write_sexp(&Sexp::List(parts), 0, &mut out);
+    out.push('\n');
+    out
+}
+
+fn atom(text: &str) -> Sexp {
+    Sexp::Atom(text.to_string())
+}
+
+fn head(kind: &str, span: Span) -> Sexp {
+    Sexp::Atom(format!("{}@{}..{}", kind, span.start, span.end))
+}
+
+fn list(kind: &str, span: Span, rest: Vec<Sexp>) -> Sexp {
+    let mut parts = vec![head(kind, span)];
+    parts.extend(rest);
+    Sexp::List(parts)
+}
+
+fn or_blank<T>(value: Option<&T>, to_sexp: impl Fn(&T) -> Sexp) -> Sexp {
+    value.map_or(atom("_"), to_sexp)
+}
+
+fn type_sexp(ty: &Type, interner: &Interner) -> Sexp {
+    atom(ty.text(interner))
+}
+
+fn item_sexp(item: &Item, interner: &Interner) -> Sexp {
+    match &item.kind {
+        ItemKind::Func { name, params, ret, body } => {
+            let mut params_sexp = vec![atom("params")];
+            params_sexp.extend(params.iter().map(|param| param_sexp(param, interner)));
+            list(
+                "func",
+                item.span,
+                vec![
+                    atom(interner.get(*name)),
+                    Sexp::List(params_sexp),
+                    or_blank(ret.as_ref(), |ty| type_sexp(ty, interner)),
+                    block_sexp(body, interner),
+                ],
+            )
+        }
+        ItemKind::Struct { name, fields } => {
+            let mut rest = vec![atom(interner.get(*name))];
+            rest.extend(fields.iter().map(|param| param_sexp(param, interner)));
+            list("struct", item.span, rest)
+        }
+        ItemKind::Import(path) => list("import", item.span, vec![atom(&unlex::spelling(&Token::LIT_STRING(*path), interner))]),
+        ItemKind::Global(stmt) => list("global", item.span, vec![stmt_sexp(stmt, interner)]),
+    }
+}
+
+fn param_sexp(param: &Param, interner: &Interner) -> Sexp {
+    list("param", param.span, vec![atom(interner.get(param.name)), type_sexp(&param.ty, interner)])
+}
+
+fn block_sexp(block: &Block, interner: &Interner) -> Sexp {
+    list("block", block.span, block.stmts.iter().map(|stmt| stmt_sexp(stmt, interner)).collect())
+}
+
+fn stmt_sexp(stmt: &Stmt, interner: &Interner) -> Sexp {
+    let span = stmt.span;
+    match &stmt.kind {
+        StmtKind::Let { name, ty, value } => list(
+            "let",
+            span,
+            vec![
+                atom(interner.get(*name)),
+                or_blank(ty.as_ref(), |ty| type_sexp(ty, interner)),
+                or_blank(value.as_ref(), |expr| expr_sexp(expr, interner)),
+            ],
+        ),
+        StmtKind::Assign { target, op, value } => {
+            list("assign", span, vec![atom(&unlex::spelling(op, interner)), expr_sexp(target, interner), expr_sexp(value, interner)])
+        }
+        StmtKind::If { cond, then_block, else_block } => list(
+            "if",
+            span,
+            vec![
+                expr_sexp(cond, interner),
+                block_sexp(then_block, interner),
+                or_blank(else_block.as_ref(), |block| block_sexp(block, interner)),
+            ],
+        ),
+        StmtKind::While { cond, body } => list("while", span, vec![expr_sexp(cond, interner), block_sexp(body, interner)]),
+        StmtKind::Return(value) => list("return", span, vec![or_blank(value.as_ref(), |expr| expr_sexp(expr, interner))]),
+        StmtKind::Print(args) => list("print", span, args.iter().map(|expr| expr_sexp(expr, interner)).collect()),
+        StmtKind::Block(block) => block_sexp(block, interner),
+        StmtKind::Expr(expr) => list("expr", span, vec![expr_sexp(expr, interner)]),
+    }
+}
+
+fn expr_sexp(expr: &Expr, interner: &Interner) -> Sexp {
+    let span = expr.span;
+    match &expr.kind {
+        ExprKind::Int(value) => list("int", span, vec![atom(&value.to_string())]),
+        ExprKind::Float(value) => list("float", span, vec![atom(&unlex::spelling(&Token::LIT_FLT32(*value), interner))]),
+        ExprKind::Char(value) => list("char", span, vec![atom(&unlex::spelling(&Token::LIT_CHAR(*value), interner))]),
+        ExprKind::Str(value) => list("str", span, vec![atom(&unlex::spelling(&Token::LIT_STRING(*value), interner))]),
+        ExprKind::Bool(value) => list("bool", span, vec![atom(&value.to_string())]),
+        ExprKind::Var(name) => list("var", span, vec![atom(interner.get(*name))]),
+        ExprKind::Unary { op, operand } => list("unary", span, vec![atom(&unlex::spelling(op, interner)), expr_sexp(operand, interner)]),
+        ExprKind::Binary { op, left, right } => {
+            list("binary", span, vec![atom(&unlex::spelling(op, interner)), expr_sexp(left, interner), expr_sexp(right, interner)])
+        }
+        ExprKind::Call { callee, args } => {
+            let mut rest = vec![expr_sexp(callee, interner)];
+            rest.extend(args.iter().map(|expr| expr_sexp(expr, interner)));
+            list("call", span, rest)
+        }
+        ExprKind::Field { object, field } => list("field", span, vec![expr_sexp(object, interner), atom(interner.get(*field))]),
+    }
+}
+
+fn inline(sexp: &Sexp) -> String {
+    match sexp {
+        Sexp::Atom(text) => text.clone(),
+        Sexp::List(parts) => format!("({})", parts.iter().map(inline).collect::<Vec<_>>().join(" ")),
+    }
+}
+
+// a list that doesn't fit on the rest of the line keeps its leading atoms
+// on the first line and puts each part after them on a line of its own
+fn write_sexp(sexp: &Sexp, indent: usize, out: &mut String) {
+    let flat = inline(sexp);
+    let Sexp::List(parts) = sexp else {
+        out.push_str(&flat);
+        return;
+    };
+    if indent + flat.len() <= SEXP_WIDTH {
+        out.push_str(&flat);
+        return;
+    }
+    let leading = parts.iter().take_while(|part| matches!(part, Sexp::Atom(_))).count();
+    out.push('(');
+    out.push_str(&parts[..leading].iter().map(inline).collect::<Vec<_>>().join(" "));
+    for part in &parts[leading..] {
+        out.push('\n');
+        out.push_str(&" ".repeat(indent + 2));
+        write_sexp(part, indent + 2, out);
+    }
+    out.push(')');
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::LexerConfig;
+    use crate::parser::parse;
+
+    const EVERY_NODE: &str = "\
+import \"lib.src\";
+struct Point [ x : flt32; y : flt32 ]
+let origin : Point;
+func main(p : Point, n : int32) -> void [
+    let c = '\\n';
+    let s : string = \"tab\\there\";
+    p.x += 2.5;
+    if !done && n >= -1 [ print f(n, true) % 3, s; ] else if false [ return; ] else [ n; ]
+    while n != 0 [ { n = n - 1; } ]
+    return p.y;
+]
+";
+
+    fn parse_ok(input: &str, interner: &mut Interner) -> Program {
+        let (program, errors) = parse(input, FileId::default(), &LexerConfig::default(), interner);
+        assert_eq!(errors, vec![]);
+        program
+    }
+
+    #[test]
+    fn json_dump_reads_back() {
+        for input in [EVERY_NODE, include_str!("input.txt")] {
+            let mut interner = Interner::new();
+            let program = parse_ok(input, &mut interner);
+            let text = json::write(&program_to_json(&program, &interner));
+            assert_eq!(program_from_json(&text, &mut interner), Ok(program));
+        }
+    }
+
+    #[test]
+    fn infinite_floats_are_written_as_strings() {
+        let mut interner = Interner::new();
+        let program = parse_ok(&format!("let x = 1{}.0;", "0".repeat(39)), &mut interner);
+        let text = json::write(&program_to_json(&program, &interner));
+        assert!(text.contains("\"value\": \"inf\""), "{}", text);
+        assert_eq!(json::parse(&text).map(|_| ()), Ok(()));
+        assert_eq!(program_from_json(&text, &mut interner), Ok(program));
+    }
+
+    #[test]
+    fn nan_floats_are_rejected_by_path() {
+        let mut interner = Interner::new();
+        let program = parse_ok("let x = 1.5;", &mut interner);
+        let text = json::write(&program_to_json(&program, &interner));
+        let nan = text.replacen("1.5", "\"NaN\"", 1);
+        let expected = "items[0].stmt.value.value: expected a number, \"inf\" or \"-inf\"";
+        assert_eq!(program_from_json(&nan, &mut interner), Err(expected.to_string()));
+        // -inf reads back and still dumps
+        let negative = program_from_json(&text.replacen("1.5", "\"-inf\"", 1), &mut interner).unwrap();
+        assert!(program_to_sexp(&negative, &interner).contains("(float@8..11 -1000"));
+        crate::dot::ast_graph(&negative, &interner);
+    }
+
+    #[test]
+    fn sexp_dump() {
+        let mut interner = Interner::new();
+        let program = parse_ok("func add(x : int32) -> int32 [ return x * (x + 1); ]\nlet s : string = \"hi\";", &mut interner);
+        assert_eq!(
+            program_to_sexp(&program, &interner),
+            "\
+(program
+  (func@0..52 add
+    (params (param@9..18 x int32))
+    int32
+    (block@29..52
+      (return@31..50
+        (binary@38..49 *
+          (var@38..39 x)
+          (binary@42..49 + (var@43..44 x) (int@47..48 1))))))
+  (global@53..75 (let@53..75 s string (str@70..74 \"hi\"))))
+"
+        );
+    }
+
+    #[test]
+    fn bad_json_asts_name_the_node() {
+        let mut interner = Interner::new();
+        let program = parse_ok("func f() [ x = 1; ]", &mut interner);
+        let text = json::write(&program_to_json(&program, &interner));
+        let broken = text.replacen("\"op\": \"=\"", "\"op\": \"+\"", 1);
+        assert_eq!(program_from_json(&broken, &mut interner), Err("items[0].body.stmts[0].op: + is not an operator here".to_string()));
+        let broken = text.replacen("\"Int\"", "\"Integer\"", 1);
+        assert!(program_from_json(&broken, &mut interner).unwrap_err().starts_with("items[0].body.stmts[0].value: unexpected node kind Integer"));
+        let broken = text.replacen("[15, 16]", "[16, 15]", 1);
+        assert_eq!(program_from_json(&broken, &mut interner), Err("items[0].body.stmts[0].value.span: expected [start, end] byte offsets".to_string()));
+        assert_eq!(program_from_json("{\"kind\": \"Program\"}", &mut interner), Err("program: missing \"items\"".to_string()));
+    }
+}
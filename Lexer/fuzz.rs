@@ -0,0 +1,362 @@
+// Fuzzing for the Lexer without cargo-fuzz. The targets have libFuzzer's
+// shape, fn(&[u8]), and panic when an invariant breaks, so they can be
+// wrapped in a fuzz_target! later. Until then `cargo run -- fuzz` drives them
+// with a small mutator seeded from the lexer snapshot inputs.
+use crate::config::LexerConfig;
+use crate::intern::Interner;
+use crate::{Lexer, Token, TokenKind, unlex};
+use std::cell::Cell;
+use std::collections::HashSet;
+use std::fs;
+use std::panic::{self, AssertUnwindSafe};
+use std::path::Path;
+use std::sync::Once;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Target {
+    Bytes, //any bytes, invalid UTF-8 is replaced before lexing
+    Utf8,  //valid UTF-8 only, mutated a char at a time
+}
+
+impl Target {
+    pub fn name(self) -> &'static str {
+        match self {
+            Target::Bytes => "bytes",
+            Target::Utf8 => "utf8",
+        }
+    }
+
+    pub fn run(self, data: &[u8]) {
+        match self {
+            Target::Bytes => lex_bytes(data),
+            Target::Utf8 => lex_utf8(data),
+        }
+    }
+}
+
+pub fn lex_bytes(data: &[u8]) {
+    lex_text(&String::from_utf8_lossy(data));
+}
+
+pub fn lex_utf8(data: &[u8]) {
+    if let Ok(text) = std::str::from_utf8(data) {
+        lex_text(text);
+    }
+}
+
+// lexes with the default language and with every operator switched off, which
+// takes the "Unexpected character" paths instead
+fn lex_text(text: &str) {
+    let no_operators = LexerConfig { operators: HashSet::new(), ..LexerConfig::default() };
+    for config in [LexerConfig::default(), no_operators] {
+        if let Err(message) = check_invariants(text, &config) {
+            panic!("{}", message);
+        }
+    }
+}
+
+// tokens come in order, each one inside the input, starting on a char
+// boundary and (except EOI) at least one byte long, with only whitespace
+// between them. An error points somewhere after the last token
+pub fn check_invariants(text: &str, config: &LexerConfig) -> Result<(), String> {
+    let mut lexer = Lexer::with_config(config.clone());
+    lexer.reset(text.to_string(), 0);
+    let mut end = 0;
+    loop {
+        let result = lexer.try_advance();
+        let (start, pos) = match &result {
+            Ok(_) => (lexer.curr_span().start, lexer.curr_span().end),
+            Err(error) => (error.pos, error.pos),
+        };
+        if start < end || pos < start || pos > text.len() {
+            return Err(format!("span {}..{} out of order after {} in {} bytes", start, pos, end, text.len()));
+        }
+        if !text.is_char_boundary(start) || !text.is_char_boundary(pos) {
+            return Err(format!("span {}..{} is not on char boundaries", start, pos));
+        }
+        if result.is_ok() && !text[end..start].chars().all(char::is_whitespace) {
+            return Err(format!("skipped {:?} before {}", &text[end..start], start));
+        }
+        match result {
+            Ok(Token::EOI) if pos == text.len() => return Ok(()),
+            Ok(Token::EOI) => return Err(format!("EOI at {} of {} bytes", pos, text.len())),
+            Ok(token) if pos == start => return Err(format!("{:?} at {} consumed nothing", token, start)),
+            Ok(_) => end = pos,
+            Err(_) => return Ok(()),
+        }
+    }
+}
+
+// small xorshift generator, so neither the fuzzer nor the tests need the rand crate
+pub struct Rng(pub u64);
+
+impl Rng {
+    #[allow(clippy::should_implement_trait)]
+    pub fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    pub fn below(&mut self, n: usize) -> usize {
+        (self.next() % n as u64) as usize
+    }
+}
+
+// the seeds when no corpus is given: every tests/lex/*.src and the sample
+// program, read from the crate directory `root` so they follow the snapshots
+pub fn default_seeds(root: &Path) -> Result<Vec<Vec<u8>>, String> {
+    let dir = root.join("tests/lex");
+    let entries = fs::read_dir(&dir).map_err(|e| format!("Error reading {}: {}", dir.display(), e))?;
+    let mut paths: Vec<_> = entries
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.extension().is_some_and(|ext| ext == "src"))
+        .collect();
+    paths.sort();
+    paths.push(root.join("input.txt"));
+    paths.iter().map(|path| fs::read(path).map_err(|e| format!("Error reading {}: {}", path.display(), e))).collect()
+}
+
+// inputs grow by splicing and repeating, but stay small enough to lex quickly
+const MAX_LEN: usize = 4096;
+
+// pieces of the language worth inserting whole
+fn dictionary() -> Vec<String> {
+    let interner = Interner::new();
+    let mut words: Vec<String> = TokenKind::ALL
+        .iter()
+        .filter_map(|kind| kind.token())
+        .map(|token| unlex::spelling(&token, &interner))
+        .collect();
+    for word in ["\"", "'", "\\", "\\x", "\\u{", "}", "1.", ".5", "_", "true", "é", "😀", "\u{FFFD}", "\n", "\t"] {
+        words.push(word.to_string());
+    }
+    words
+}
+
+struct Mutator {
+    rng: Rng,
+    dictionary: Vec<String>,
+}
+
+impl Mutator {
+    fn mutate(&mut self, target: Target, input: &[u8], corpus: &[Vec<u8>]) -> Vec<u8> {
+        let mut data = input.to_vec();
+        for _ in 0..1 + self.rng.below(4) {
+            data = match target {
+                Target::Bytes => self.mutate_bytes(data, corpus),
+                Target::Utf8 => self.mutate_chars(data, corpus),
+            };
+        }
+        data.truncate(MAX_LEN);
+        if target == Target::Utf8 {
+            // truncating may have split the last char
+            data = String::from_utf8_lossy(&data).into_owned().into_bytes();
+        }
+        data
+    }
+
+    // a random range of `len` items
+    fn range(&mut self, len: usize) -> (usize, usize) {
+        let start = self.rng.below(len + 1);
+        let end = start + self.rng.below(len - start + 1).min(16);
+        (start, end)
+    }
+
+    fn mutate_bytes(&mut self, mut data: Vec<u8>, corpus: &[Vec<u8>]) -> Vec<u8> {
+        let at = self.rng.below(data.len() + 1);
+        match self.rng.below(6) {
+            0 if !data.is_empty() => {
+                let i = self.rng.below(data.len());
+                data[i] ^= 1 << self.rng.below(8);
+            }
+            1 => data.insert(at, self.rng.next() as u8),
+            2 => {
+                let (start, end) = self.range(data.len());
+                data.drain(start..end);
+            }
+            3 => {
+                let word = self.dictionary[self.rng.below(self.dictionary.len())].clone();
+                data.splice(at..at, word.into_bytes());
+            }
+            _ => return self.splice_or_repeat(data, corpus),
+        }
+        data
+    }
+
+    fn mutate_chars(&mut self, data: Vec<u8>, corpus: &[Vec<u8>]) -> Vec<u8> {
+        let mut chars: Vec<char> = String::from_utf8_lossy(&data).chars().collect();
+        let at = self.rng.below(chars.len() + 1);
+        match self.rng.below(5) {
+            0 => {
+                // mostly ASCII, sometimes any char up to the emoji blocks
+                let c = if self.rng.below(4) == 0 {
+                    char::from_u32(self.rng.below(0x1F700) as u32).unwrap_or('\u{FFFD}')
+                } else {
+                    char::from(self.rng.below(128) as u8)
+                };
+                chars.insert(at, c);
+            }
+            1 => {
+                let (start, end) = self.range(chars.len());
+                chars.drain(start..end);
+            }
+            2 => {
+                let word = &self.dictionary[self.rng.below(self.dictionary.len())];
+                chars.splice(at..at, word.chars());
+            }
+            _ => return self.splice_or_repeat(chars.into_iter().collect::<String>().into_bytes(), corpus),
+        }
+        chars.into_iter().collect::<String>().into_bytes()
+    }
+
+    // copies part of another corpus entry in, or repeats part of this one to
+    // build long tokens. Only cuts at ASCII bytes so UTF-8 stays valid
+    fn splice_or_repeat(&mut self, mut data: Vec<u8>, corpus: &[Vec<u8>]) -> Vec<u8> {
+        let source = if self.rng.below(2) == 0 { data.clone() } else { corpus[self.rng.below(corpus.len())].clone() };
+        let cuts: Vec<usize> = (0..=source.len()).filter(|&i| i == source.len() || source[i].is_ascii()).collect();
+        let start = cuts[self.rng.below(cuts.len())];
+        let end = cuts[self.rng.below(cuts.len())].max(start);
+        let piece = &source[start..end];
+        let at = (0..=data.len())
+            .filter(|&i| i == data.len() || data[i].is_ascii())
+            .nth(self.rng.below(data.len() + 1))
+            .unwrap_or(data.len());
+        let times = if self.rng.below(4) == 0 { 1 + self.rng.below(64) } else { 1 };
+        for _ in 0..times {
+            data.splice(at..at, piece.iter().copied());
+        }
+        data
+    }
+}
+
+// what an input made the lexer do, a stand-in for coverage: inputs that lex
+// a new pair of neighbouring token kinds or hit a new error are kept
+fn features(data: &[u8]) -> Vec<String> {
+    let text = String::from_utf8_lossy(data);
+    let mut lexer = Lexer::new();
+    lexer.reset(text.into_owned(), 0);
+    let mut features = Vec::new();
+    let mut previous = TokenKind::EOI;
+    loop {
+        match lexer.try_advance() {
+            Ok(Token::EOI) => return features,
+            Ok(token) => {
+                features.push(format!("{:?} {:?}", previous, token.kind()));
+                previous = token.kind();
+            }
+            Err(error) => {
+                // drop the offending char and unicode value from the message
+                features.push(error.message.split(':').next().unwrap_or("").to_string());
+                return features;
+            }
+        }
+    }
+}
+
+pub struct Failure {
+    pub input: Vec<u8>,
+    pub message: String,
+}
+
+pub struct Report {
+    pub runs: usize,
+    pub corpus: usize,
+    pub failure: Option<Failure>,
+}
+
+// runs the target on the seeds, then on `runs` mutated inputs. Stops at the
+// first panic
+pub fn fuzz(target: Target, seeds: Vec<Vec<u8>>, runs: usize, seed: u64) -> Report {
+    let mut corpus = if seeds.is_empty() { vec![Vec::new()] } else { seeds };
+    let mut mutator = Mutator { rng: Rng(seed.max(1)), dictionary: dictionary() };
+    let mut seen: HashSet<String> = corpus.iter().flat_map(|input| features(input)).collect();
+
+    quiet_panics(true);
+    let mut report = Report { runs: 0, corpus: corpus.len(), failure: None };
+    let mut queue = corpus.clone();
+    let total = runs + corpus.len();
+    while report.failure.is_none() && report.runs < total {
+        let input = match queue.pop() {
+            Some(input) => input,
+            None => {
+                let parent = corpus[mutator.rng.below(corpus.len())].clone();
+                mutator.mutate(target, &parent, &corpus)
+            }
+        };
+        report.runs += 1;
+        match panic::catch_unwind(AssertUnwindSafe(|| target.run(&input))) {
+            Ok(()) => {
+                let new: Vec<String> = features(&input).into_iter().filter(|f| !seen.contains(f)).collect();
+                if !new.is_empty() {
+                    seen.extend(new);
+                    corpus.push(input);
+                }
+            }
+            Err(payload) => {
+                let message = payload
+                    .downcast_ref::<String>()
+                    .cloned()
+                    .or_else(|| payload.downcast_ref::<&str>().map(|s| s.to_string()))
+                    .unwrap_or_else(|| "panic".to_string());
+                report.failure = Some(Failure { input, message });
+            }
+        }
+    }
+    quiet_panics(false);
+    report.corpus = corpus.len();
+    report
+}
+
+thread_local! {
+    static QUIET: Cell<bool> = const { Cell::new(false) };
+}
+
+// the default hook would print every caught panic. The hook is shared by the
+// whole process, so it stays installed and only keeps quiet on threads that
+// are fuzzing, other threads (like parallel tests) still report their panics
+fn quiet_panics(quiet: bool) {
+    static INSTALL: Once = Once::new();
+    INSTALL.call_once(|| {
+        let hook = panic::take_hook();
+        panic::set_hook(Box::new(move |info| {
+            if !QUIET.with(Cell::get) {
+                hook(info);
+            }
+        }));
+    });
+    QUIET.with(|q| q.set(quiet));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn invariants_hold_on_tricky_inputs() {
+        let long_string = format!("\"{}\"", "a".repeat(5000));
+        let long_id = "x".repeat(3000);
+        for input in ["", "  ", "é", "\"\\u{", "'", "1.", "a\u{85}b", "!=!<!>", long_string.as_str(), long_id.as_str()] {
+            lex_bytes(input.as_bytes());
+            lex_utf8(input.as_bytes());
+        }
+        lex_bytes(&[0xff, b'"', 0xc3]);
+        let mut interner = Interner::new();
+        let tokens = Lexer::tokenize(&long_string, &mut interner).unwrap();
+        assert_eq!(tokens[0].token, Token::LIT_STRING(interner.intern(&"a".repeat(5000))));
+    }
+
+    #[test]
+    fn short_campaign_over_the_seed_corpus() {
+        let seeds = default_seeds(Path::new(env!("CARGO_MANIFEST_DIR"))).unwrap();
+        assert!(seeds.len() > 1);
+        for target in [Target::Bytes, Target::Utf8] {
+            let report = fuzz(target, seeds.clone(), 3000, 7);
+            if let Some(failure) = report.failure {
+                panic!("{}: {} on {:?}", target.name(), failure.message, String::from_utf8_lossy(&failure.input));
+            }
+            assert!(report.corpus > seeds.len());
+        }
+    }
+}
@@ -0,0 +1,139 @@
+// Snapshot tests for the Lexer and the parser. Every tests/lex/<name>.src is
+// lexed and compared against <name>.tokens, one "line:col TOKEN" per token,
+// and, when lexing stops with an error, <name>.err. Every tests/parse/<name>.src
+// is compared against <name>.check, what `cargo run -- check` prints for it.
+// A <name>.json next to a source is used as its dialect.
+//
+// Run with BLESS=1 to rewrite the snapshots from the current output:
+//     BLESS=1 cargo test snapshots
+use crate::config::LexerConfig;
+use crate::intern::Interner;
+use crate::parser;
+use crate::source::SourceMap;
+use crate::{Lexer, Token, TokenKind, TokenSet, check_report};
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+// the .tokens and .err text for one source file
+fn lex_snapshot(name: &str, text: &str, config: LexerConfig) -> (String, Option<String>) {
+    let mut sources = SourceMap::new();
+    let file = sources.add_file(name, text.to_string());
+    let mut lexer = Lexer::with_config(config);
+    lexer.set_file(file);
+    lexer.reset(text.to_string(), 0);
+    let mut tokens = String::new();
+    loop {
+        match lexer.try_advance() {
+            Ok(token) => {
+                let (line, column) = sources.line_col(file, lexer.curr_span().start);
+                tokens.push_str(&format!("{}:{} {}\n", line, column, lexer.interner().debug(&token)));
+                if token == Token::EOI {
+                    return (tokens, None);
+                }
+            }
+            Err(error) => return (tokens, Some(format!("{}\n", sources.describe_lex_error(&error)))),
+        }
+    }
+}
+
+// compares `actual` with the snapshot at `path`, or rewrites it when blessing.
+// A missing snapshot stands for "no output", which is how sources that lex
+// cleanly go without an .err file
+fn check(path: &Path, actual: Option<&str>, bless: bool, failures: &mut Vec<String>) {
+    let expected = fs::read_to_string(path).ok();
+    if expected.as_deref() == actual {
+        return;
+    }
+    if bless {
+        match actual {
+            Some(text) => fs::write(path, text).unwrap(),
+            None => fs::remove_file(path).unwrap(),
+        }
+        return;
+    }
+    let message = match (expected.as_deref(), actual) {
+        (None, _) => "missing".to_string(),
+        (Some(_), None) => "not produced any more".to_string(),
+        (Some(expected), Some(actual)) => {
+            let expected_lines: Vec<&str> = expected.lines().collect();
+            let actual_lines: Vec<&str> = actual.lines().collect();
+            let line = (0..)
+                .find(|&i| expected_lines.get(i) != actual_lines.get(i))
+                .unwrap();
+            format!(
+                "differs at line {}\n    expected: {}\n    actual:   {}",
+                line + 1,
+                expected_lines.get(line).unwrap_or(&"<end of file>"),
+                actual_lines.get(line).unwrap_or(&"<end of file>")
+            )
+        }
+    };
+    failures.push(format!("{}: {}", path.display(), message));
+}
+
+fn sources(dir: &Path) -> Vec<PathBuf> {
+    let mut paths: Vec<PathBuf> = fs::read_dir(dir)
+        .unwrap_or_else(|e| panic!("Error reading {}: {}", dir.display(), e))
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "src"))
+        .collect();
+    paths.sort();
+    paths
+}
+
+fn dialect(source: &Path) -> LexerConfig {
+    let path = source.with_extension("json");
+    if path.exists() { LexerConfig::load(&path.to_string_lossy()).unwrap() } else { LexerConfig::default() }
+}
+
+fn assert_no_failures(failures: &[String]) {
+    assert!(
+        failures.is_empty(),
+        "{} snapshot(s) out of date, rerun with BLESS=1 if the change is intended:\n{}",
+        failures.len(),
+        failures.join("\n")
+    );
+}
+
+#[test]
+fn snapshots() {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/lex");
+    let bless = env::var_os("BLESS").is_some();
+    let mut failures = Vec::new();
+    let mut seen = TokenSet::EMPTY;
+
+    for path in sources(&dir) {
+        let name = path.file_name().unwrap().to_string_lossy().into_owned();
+        let text = fs::read_to_string(&path).unwrap();
+        let config = dialect(&path);
+        let (tokens, error) = lex_snapshot(&name, &text, config.clone());
+        let lexed = Lexer::tokenize_with_config(&text, &config, &mut Interner::new());
+        assert_eq!(error.is_none(), lexed.is_ok(), "{} lexes differently through tokenize", name);
+        for token in lexed.unwrap_or_default() {
+            seen = seen.with(token.token.kind());
+        }
+        check(&path.with_extension("tokens"), Some(&tokens), bless, &mut failures);
+        check(&path.with_extension("err"), error.as_deref(), bless, &mut failures);
+    }
+
+    assert_no_failures(&failures);
+    let missing: Vec<TokenKind> = TokenKind::ALL.iter().copied().filter(|&kind| !seen.contains(kind)).collect();
+    assert!(missing.is_empty(), "no snapshot lexes {:?}", missing);
+}
+
+#[test]
+fn parse_snapshots() {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/parse");
+    let bless = env::var_os("BLESS").is_some();
+    let mut failures = Vec::new();
+    for path in sources(&dir) {
+        let name = path.file_name().unwrap().to_string_lossy().into_owned();
+        let text = fs::read_to_string(&path).unwrap();
+        let mut sources = SourceMap::new();
+        let file = sources.add_file(&name, text);
+        let (_, errors) = parser::parse(&sources.file(file).text, file, &dialect(&path), &mut Interner::new());
+        check(&path.with_extension("check"), Some(&check_report(&name, &sources, &errors)), bless, &mut failures);
+    }
+    assert_no_failures(&failures);
+}
@@ -0,0 +1,165 @@
+// Incremental re-lexing for editors: re-lex only the tokens an edit can affect
+// and splice them into the previous token vector.
+use crate::intern::Interner;
+use crate::{LexError, Lexer, Span, SpannedToken};
+
+// the lexer looks at most two chars (peek_char + peek_next_char) past the end
+// of a token, each up to 4 bytes of UTF-8
+const LOOKAHEAD: usize = 8;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TextEdit {
+    pub start: usize, //byte range in the old text that gets replaced
+    pub end: usize,
+    pub replacement: String,
+}
+
+impl TextEdit {
+    pub fn apply(&self, text: &str) -> String {
+        let mut result = String::with_capacity(text.len() + self.replacement.len());
+        result.push_str(&text[..self.start]);
+        result.push_str(&self.replacement);
+        result.push_str(&text[self.end..]);
+        result
+    }
+
+    // how far text after the edit moves
+    fn shift(&self, pos: usize) -> usize {
+        pos + self.replacement.len() - (self.end - self.start)
+    }
+}
+
+// old_tokens must come from Lexer::tokenize on the text before the edit, with
+// the same interner, and new_text is that text with the edit applied. Gives
+// the same result as Lexer::tokenize(new_text).
+pub fn relex(
+    old_tokens: &[SpannedToken],
+    new_text: &str,
+    edit: &TextEdit,
+    interner: &mut Interner,
+) -> Result<Vec<SpannedToken>, LexError> {
+    // tokens that never looked at the edited bytes are kept as they are
+    let kept = old_tokens
+        .iter()
+        .take_while(|t| t.token != crate::Token::EOI && t.span.end + LOOKAHEAD <= edit.start)
+        .count();
+    let restart = if kept == 0 { 0 } else { old_tokens[kept - 1].span.end };
+
+    let mut tokens: Vec<SpannedToken> = old_tokens[..kept].to_vec();
+    let mut lexer = Lexer::new();
+    lexer.set_file(old_tokens.first().map(|t| t.span.file).unwrap_or_default());
+    lexer.reset(new_text.to_string(), restart);
+    lexer.set_interner(std::mem::take(interner));
+
+    // old tokens starting after the edit are unchanged apart from their offset,
+    // so once the new tokens reach one of their starts the rest can be reused
+    let mut old = kept;
+    let result = loop {
+        let pos = lexer.next_token_start();
+        while old < old_tokens.len()
+            && (old_tokens[old].span.start < edit.end || edit.shift(old_tokens[old].span.start) < pos)
+        {
+            old += 1;
+        }
+        if old < old_tokens.len() && edit.shift(old_tokens[old].span.start) == pos {
+            tokens.extend(old_tokens[old..].iter().map(|t| SpannedToken {
+                token: t.token,
+                span: Span { file: t.span.file, start: edit.shift(t.span.start), end: edit.shift(t.span.end) },
+            }));
+            break Ok(tokens);
+        }
+
+        let token = match lexer.try_advance() {
+            Ok(token) => token,
+            Err(error) => break Err(error),
+        };
+        let done = token == crate::Token::EOI;
+        tokens.push(SpannedToken { token, span: lexer.curr_span() });
+        if done {
+            break Ok(tokens);
+        }
+    };
+    *interner = lexer.take_interner();
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fuzz::Rng;
+
+    const PROGRAM: &str = "func add(x : int32) -> int32\n[\n\tlet value : int32 = 35;\n\tvalue = value + x;\n\treturn value;\n]\n\nfunc main()\n[\n\tlet sum : int32 = 0;\n\tsum = add(2) + 5;\n\tlet s : string = \"sum=\\t\";\n\tprint s , sum, 'c', 1.5;\n]\n";
+
+    const SNIPPETS: &[&str] = &[
+        "\"", "\"\"", "\"a b\"", "'", "'x'", "\\", "\\\"", " ", "\n", "x", "ab", "_1", "1", "23",
+        ".", ".5", "-", ">", "<", "=", "!", "&&", "||", "func", "let", "é", "[", "]", ";",
+    ];
+
+    fn random_edit(rng: &mut Rng, text: &str) -> TextEdit {
+        let boundaries: Vec<usize> = text
+            .char_indices()
+            .map(|(i, _)| i)
+            .chain(std::iter::once(text.len()))
+            .collect();
+        let start = boundaries[rng.below(boundaries.len())];
+        let end = if rng.below(2) == 0 {
+            start
+        } else {
+            let later: Vec<usize> = boundaries.iter().copied().filter(|&b| b >= start && b <= start + 6).collect();
+            later[rng.below(later.len())]
+        };
+        let replacement = if rng.below(4) == 0 {
+            String::new()
+        } else {
+            SNIPPETS[rng.below(SNIPPETS.len())].to_string()
+        };
+        TextEdit { start, end, replacement }
+    }
+
+    #[test]
+    fn random_edits_match_full_relex() {
+        let mut rng = Rng(0x2545F4914F6CDD1D);
+        let mut interner = Interner::new();
+        let mut text = PROGRAM.to_string();
+        let mut tokens = Lexer::tokenize(&text, &mut interner).unwrap();
+        let mut accepted = 0;
+        for _ in 0..5000 {
+            let edit = random_edit(&mut rng, &text);
+            let new_text = edit.apply(&text);
+            let full = Lexer::tokenize(&new_text, &mut interner);
+            let incremental = relex(&tokens, &new_text, &edit, &mut interner);
+            assert_eq!(incremental, full, "edit {:?} on {:?}", edit, text);
+            // keep walking from texts that still lex, and start over now and then
+            if let Ok(new_tokens) = full {
+                text = new_text;
+                tokens = new_tokens;
+                accepted += 1;
+            }
+            if rng.below(200) == 0 {
+                text = PROGRAM.to_string();
+                tokens = Lexer::tokenize(&text, &mut interner).unwrap();
+            }
+        }
+        assert!(accepted > 1000);
+    }
+
+    #[test]
+    fn opening_and_closing_a_string() {
+        let mut interner = Interner::new();
+        let text = "let a = b; print \"x\";";
+        let tokens = Lexer::tokenize(text, &mut interner).unwrap();
+
+        // a quote before `b` turns `b; print ` into a string and leaves `x"` open
+        let open = TextEdit { start: 8, end: 8, replacement: "\"".to_string() };
+        let opened = open.apply(text);
+        assert_eq!(relex(&tokens, &opened, &open, &mut interner), Lexer::tokenize(&opened, &mut interner));
+        assert!(Lexer::tokenize(&opened, &mut interner).is_err());
+
+        // closing it again right after `b` gives a string literal and the old tail
+        let close = TextEdit { start: 8, end: 9, replacement: "\"\"".to_string() };
+        let closed = close.apply(text);
+        let relexed = relex(&tokens, &closed, &close, &mut interner).unwrap();
+        assert_eq!(relexed, Lexer::tokenize(&closed, &mut interner).unwrap());
+        assert_eq!(relexed[3].token, crate::Token::LIT_STRING(interner.intern("")));
+    }
+}
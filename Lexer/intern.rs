@@ -0,0 +1,77 @@
+// Interned strings. Identifiers and string literals are stored once in an
+// Interner and handed around as a Symbol, an index into it, so tokens are
+// Copy and comparing two names compares two integers. Each Lexer owns the
+// Interner its symbols point into; callers that lex or parse several times
+// pass one in and get it back, and the strings are freed with it.
+use crate::Token;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Symbol(u32);
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Interner {
+    symbols: HashMap<Rc<str>, Symbol>,
+    strings: Vec<Rc<str>>,
+}
+
+impl Interner {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // only allocates the first time `text` is seen
+    pub fn intern(&mut self, text: &str) -> Symbol {
+        if let Some(&symbol) = self.symbols.get(text) {
+            return symbol;
+        }
+        let symbol = Symbol(u32::try_from(self.strings.len()).expect("more than u32::MAX interned strings"));
+        let text: Rc<str> = Rc::from(text);
+        self.strings.push(Rc::clone(&text));
+        self.symbols.insert(text, symbol);
+        symbol
+    }
+
+    // panics for a Symbol from another Interner that is out of range here
+    pub fn get(&self, symbol: Symbol) -> &str {
+        &self.strings[symbol.0 as usize]
+    }
+
+    pub fn len(&self) -> usize {
+        self.strings.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.strings.is_empty()
+    }
+
+    // a token's Debug form with its symbol spelled out, ID("x") rather than
+    // ID(Symbol(0))
+    pub fn debug(&self, token: &Token) -> String {
+        match token {
+            Token::ID(symbol) => format!("ID({:?})", self.get(*symbol)),
+            Token::LIT_STRING(symbol) => format!("LIT_STRING({:?})", self.get(*symbol)),
+            _ => format!("{:?}", token),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn same_text_same_symbol() {
+        let mut interner = Interner::new();
+        let count = interner.intern("count");
+        assert_eq!(count, interner.intern(&String::from("count")));
+        assert_ne!(count, interner.intern("counts"));
+        assert_eq!(interner.get(count), "count");
+        assert_eq!(interner.len(), 2);
+        let quoted = interner.intern("say \"hi\"");
+        assert_eq!(interner.debug(&Token::LIT_STRING(quoted)), "LIT_STRING(\"say \\\"hi\\\"\")");
+        assert_eq!(interner.debug(&Token::ID(count)), "ID(\"count\")");
+        assert_eq!(interner.debug(&Token::ARROW_R), "ARROW_R");
+    }
+}
@@ -0,0 +1,968 @@
+// Static single assignment form of each function, lowered from its basic
+// blocks (cfg.rs). Every assignment to a local variable makes a new value, and
+// where values of one variable from different paths meet a phi picks between
+// them. Phis go on the iterated dominance frontier of the blocks assigning a
+// variable (Cytron et al.), and only for variables some block reads before
+// assigning them, the rest never live from one block into another.
+//
+// Names that aren't a parameter or a `let` of the function are globals and are
+// read and written by name. `&&` and `||` are plain instructions, they don't
+// split blocks. Blocks control can't reach are left out.
+use crate::ast::*;
+use crate::cfg::{self, Exit};
+use crate::intern::{Interner, Symbol};
+use crate::{Span, Token, unlex};
+use std::collections::HashMap;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Value(pub usize);
+
+impl Value {
+    pub fn text(self) -> String {
+        format!("%{}", self.0)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum InstKind {
+    Const(Token), //a literal
+    Param(Symbol),
+    Undef, //a variable read where nothing was assigned to it yet
+    Global(Symbol),
+    Unary { op: Token, operand: Value },
+    Binary { op: Token, left: Value, right: Value },
+    Call { callee: String, args: Vec<Value> }, //the callee as written, e.g. math.add
+    Field { object: Value, field: Symbol },
+    SetGlobal { name: Symbol, value: Value },
+    SetField { object: Value, field: Symbol, value: Value },
+    Print(Vec<Value>),
+}
+
+impl InstKind {
+    pub fn operands(&self) -> Vec<Value> {
+        match self {
+            InstKind::Const(_) | InstKind::Param(_) | InstKind::Undef | InstKind::Global(_) => Vec::new(),
+            InstKind::Unary { operand, .. } => vec![*operand],
+            InstKind::Binary { left, right, .. } => vec![*left, *right],
+            InstKind::Call { args, .. } | InstKind::Print(args) => args.clone(),
+            InstKind::Field { object, .. } => vec![*object],
+            InstKind::SetGlobal { value, .. } => vec![*value],
+            InstKind::SetField { object, value, .. } => vec![*object, *value],
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Inst {
+    pub dest: Option<Value>, //None for the stores and print
+    pub kind: InstKind,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Phi {
+    pub dest: Value,
+    pub var: Symbol, //the variable it merges, for dumps
+    pub args: Vec<(usize, Value)>, //the value coming from each predecessor block
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum IrExit {
+    Goto(usize),
+    Branch { cond: Value, then: usize, other: usize },
+    Return(Option<Value>),
+    End, //falls off the end of the function
+}
+
+impl IrExit {
+    pub fn targets(&self) -> Vec<usize> {
+        match *self {
+            IrExit::Goto(to) => vec![to],
+            IrExit::Branch { then, other, .. } => vec![then, other],
+            IrExit::Return(_) | IrExit::End => Vec::new(),
+        }
+    }
+
+    fn operand(&self) -> Option<Value> {
+        match *self {
+            IrExit::Branch { cond, .. } => Some(cond),
+            IrExit::Return(value) => value,
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct IrBlock {
+    pub phis: Vec<Phi>,
+    pub insts: Vec<Inst>,
+    pub exit: IrExit,
+}
+
+// blocks[0] is the entry. Values are numbered from 0 up to `values`
+#[derive(Debug, Clone, PartialEq)]
+pub struct IrFunction {
+    pub name: Symbol,
+    pub blocks: Vec<IrBlock>,
+    pub values: usize,
+}
+
+pub fn lower_program(program: &Program, interner: &Interner) -> Vec<IrFunction> {
+    let mut functions = Vec::new();
+    for item in &program.items {
+        if let ItemKind::Func { name, params, body, .. } = &item.kind {
+            functions.push(lower_function(*name, params, body, interner));
+        }
+    }
+    functions
+}
+
+// ---------------------------------------------------------------------------
+// dominance
+
+fn predecessors(succs: &[Vec<usize>]) -> Vec<Vec<usize>> {
+    let mut preds = vec![Vec::new(); succs.len()];
+    for (b, targets) in succs.iter().enumerate() {
+        for &to in targets {
+            if !preds[to].contains(&b) {
+                preds[to].push(b);
+            }
+        }
+    }
+    preds
+}
+
+// the blocks reachable from the entry, in reverse postorder
+fn reverse_postorder(succs: &[Vec<usize>]) -> Vec<usize> {
+    let mut visited = vec![false; succs.len()];
+    let mut order = Vec::new();
+    // each block with how many of its successors have been looked at
+    let mut stack = vec![(0, 0)];
+    visited[0] = true;
+    while let Some((b, next)) = stack.pop() {
+        match succs[b].get(next) {
+            Some(&to) => {
+                stack.push((b, next + 1));
+                if !visited[to] {
+                    visited[to] = true;
+                    stack.push((to, 0));
+                }
+            }
+            None => order.push(b),
+        }
+    }
+    order.reverse();
+    order
+}
+
+// the immediate dominator of each block, the entry's being itself and None for
+// blocks the entry doesn't reach. Cooper, Harvey and Kennedy's "A Simple, Fast
+// Dominance Algorithm"
+fn immediate_dominators(succs: &[Vec<usize>]) -> Vec<Option<usize>> {
+    let order = reverse_postorder(succs);
+    let preds = predecessors(succs);
+    let mut rank = vec![usize::MAX; succs.len()];
+    for (i, &b) in order.iter().enumerate() {
+        rank[b] = i;
+    }
+    let mut idom = vec![None; succs.len()];
+    idom[0] = Some(0);
+    let mut changed = true;
+    while changed {
+        changed = false;
+        for &b in &order[1..] {
+            let mut new = None;
+            for &p in preds[b].iter().filter(|&&p| idom[p].is_some()) {
+                new = Some(match new {
+                    None => p,
+                    Some(other) => intersect(p, other, &idom, &rank),
+                });
+            }
+            if idom[b] != new {
+                idom[b] = new;
+                changed = true;
+            }
+        }
+    }
+    idom
+}
+
+// the closest block dominating both `a` and `b`
+fn intersect(mut a: usize, mut b: usize, idom: &[Option<usize>], rank: &[usize]) -> usize {
+    while a != b {
+        while rank[a] > rank[b] {
+            a = idom[a].unwrap();
+        }
+        while rank[b] > rank[a] {
+            b = idom[b].unwrap();
+        }
+    }
+    a
+}
+
+fn dominates(idom: &[Option<usize>], a: usize, mut b: usize) -> bool {
+    loop {
+        if a == b {
+            return true;
+        }
+        let up = idom[b].unwrap();
+        if up == b {
+            return false;
+        }
+        b = up;
+    }
+}
+
+// the blocks where the dominance of each block ends: those it doesn't strictly
+// dominate but one of whose predecessors it dominates
+fn dominance_frontiers(preds: &[Vec<usize>], idom: &[Option<usize>]) -> Vec<Vec<usize>> {
+    let mut frontiers: Vec<Vec<usize>> = vec![Vec::new(); preds.len()];
+    for (b, from) in preds.iter().enumerate().filter(|(_, from)| from.len() > 1) {
+        let stop = idom[b].unwrap();
+        for &p in from {
+            let mut runner = p;
+            while runner != stop && !frontiers[runner].contains(&b) {
+                frontiers[runner].push(b);
+                runner = idom[runner].unwrap();
+            }
+        }
+    }
+    frontiers
+}
+
+// ---------------------------------------------------------------------------
+// variables
+
+// which local variable each `let` declares and each name reads, looked up by
+// their spans. A name with no entry is a global
+#[derive(Default)]
+struct Scopes {
+    names: Vec<Symbol>, //of every variable, a variable being its index
+    visible: Vec<usize>, //innermost last
+    declared: HashMap<Span, usize>,
+    refers: HashMap<Span, usize>,
+}
+
+impl Scopes {
+    fn declare(&mut self, name: Symbol) -> usize {
+        self.names.push(name);
+        self.visible.push(self.names.len() - 1);
+        self.names.len() - 1
+    }
+
+    fn block(&mut self, stmts: &[Stmt]) {
+        let depth = self.visible.len();
+        for stmt in stmts {
+            self.stmt(stmt);
+        }
+        self.visible.truncate(depth);
+    }
+
+    fn stmt(&mut self, stmt: &Stmt) {
+        match &stmt.kind {
+            StmtKind::Let { name, value, .. } => {
+                // `let x = x;` reads the x from before
+                if let Some(value) = value {
+                    self.expr(value);
+                }
+                let var = self.declare(*name);
+                self.declared.insert(stmt.span, var);
+            }
+            StmtKind::Assign { target, value, .. } => {
+                self.expr(target);
+                self.expr(value);
+            }
+            StmtKind::If { cond, then_block, else_block } => {
+                self.expr(cond);
+                self.block(&then_block.stmts);
+                if let Some(block) = else_block {
+                    self.block(&block.stmts);
+                }
+            }
+            StmtKind::While { cond, body } => {
+                self.expr(cond);
+                self.block(&body.stmts);
+            }
+            StmtKind::Return(value) => {
+                if let Some(value) = value {
+                    self.expr(value);
+                }
+            }
+            StmtKind::Print(args) => args.iter().for_each(|arg| self.expr(arg)),
+            StmtKind::Block(block) => self.block(&block.stmts),
+            StmtKind::Expr(expr) => self.expr(expr),
+        }
+    }
+
+    fn expr(&mut self, expr: &Expr) {
+        match &expr.kind {
+            ExprKind::Var(name) => {
+                if let Some(&var) = self.visible.iter().rev().find(|&&var| self.names[var] == *name) {
+                    self.refers.insert(expr.span, var);
+                }
+            }
+            ExprKind::Unary { operand, .. } => self.expr(operand),
+            ExprKind::Binary { left, right, .. } => {
+                self.expr(left);
+                self.expr(right);
+            }
+            // the callee is kept as written
+            ExprKind::Call { args, .. } => args.iter().for_each(|arg| self.expr(arg)),
+            ExprKind::Field { object, .. } => self.expr(object),
+            _ => {}
+        }
+    }
+
+    // the local variables `expr` reads, in the order it reads them
+    fn reads(&self, expr: &Expr, out: &mut Vec<usize>) {
+        match &expr.kind {
+            ExprKind::Var(_) => out.extend(self.refers.get(&expr.span)),
+            ExprKind::Unary { operand, .. } => self.reads(operand, out),
+            ExprKind::Binary { left, right, .. } => {
+                self.reads(left, out);
+                self.reads(right, out);
+            }
+            ExprKind::Call { args, .. } => args.iter().for_each(|arg| self.reads(arg, out)),
+            ExprKind::Field { object, .. } => self.reads(object, out),
+            _ => {}
+        }
+    }
+}
+
+// ---------------------------------------------------------------------------
+// lowering
+
+pub fn lower_function(name: Symbol, params: &[Param], body: &Block, interner: &Interner) -> IrFunction {
+    let mut scopes = Scopes::default();
+    for param in params {
+        scopes.declare(param.name);
+    }
+    scopes.block(&body.stmts);
+
+    // the blocks control reaches, in their order in the CFG
+    let cfg = cfg::function_cfg(name, body);
+    let cfg_succs: Vec<Vec<usize>> = cfg.blocks.iter().map(|block| exit_targets(&block.exit)).collect();
+    let mut reached = vec![false; cfg.blocks.len()];
+    for b in reverse_postorder(&cfg_succs) {
+        reached[b] = true;
+    }
+    let mut new_index = Vec::new();
+    let mut next = 0;
+    for &kept in &reached {
+        new_index.push(next);
+        next += kept as usize;
+    }
+    let cfg_blocks: Vec<cfg::BasicBlock> =
+        cfg.blocks.into_iter().zip(&reached).filter(|(_, reached)| **reached).map(|(block, _)| block).collect();
+    let succs: Vec<Vec<usize>> =
+        cfg_blocks.iter().map(|block| exit_targets(&block.exit).into_iter().map(|to| new_index[to]).collect()).collect();
+
+    let preds = predecessors(&succs);
+    let idom = immediate_dominators(&succs);
+    let frontiers = dominance_frontiers(&preds, &idom);
+    let mut children = vec![Vec::new(); succs.len()];
+    for b in 1..succs.len() {
+        children[idom[b].unwrap()].push(b);
+    }
+
+    // where each variable is assigned, and whether it is read in a block
+    // before being assigned there, so lives from one block into another
+    let vars = scopes.names.len();
+    let mut assigned_in = vec![Vec::new(); vars];
+    let mut crosses_blocks = vec![false; vars];
+    for blocks in &mut assigned_in[..params.len()] {
+        blocks.push(0);
+    }
+    for (b, block) in cfg_blocks.iter().enumerate() {
+        let mut assigned = vec![false; vars];
+        let mut reads = Vec::new();
+        let mut read = |reads: &mut Vec<usize>, assigned: &[bool]| {
+            for var in reads.drain(..) {
+                crosses_blocks[var] |= !assigned[var];
+            }
+        };
+        for stmt in &block.stmts {
+            let written = match &stmt.kind {
+                StmtKind::Let { value, .. } => {
+                    if let Some(value) = value {
+                        scopes.reads(value, &mut reads);
+                    }
+                    scopes.declared.get(&stmt.span).copied()
+                }
+                StmtKind::Assign { target, op, value } => {
+                    scopes.reads(value, &mut reads);
+                    let var = scopes.refers.get(&target.span).copied();
+                    if var.is_none() || *op != Token::ASSIGN {
+                        scopes.reads(target, &mut reads);
+                    }
+                    var
+                }
+                StmtKind::Print(args) => {
+                    args.iter().for_each(|arg| scopes.reads(arg, &mut reads));
+                    None
+                }
+                StmtKind::Expr(expr) => {
+                    scopes.reads(expr, &mut reads);
+                    None
+                }
+                _ => unreachable!("cfg.rs ends a block at {:?}", stmt.kind),
+            };
+            read(&mut reads, &assigned);
+            if let Some(var) = written {
+                assigned[var] = true;
+                if !assigned_in[var].contains(&b) {
+                    assigned_in[var].push(b);
+                }
+            }
+        }
+        match &block.exit {
+            Exit::Branch { cond: value, .. } | Exit::Return(Some(value)) => scopes.reads(value, &mut reads),
+            _ => {}
+        }
+        read(&mut reads, &assigned);
+    }
+
+    // phis on the iterated dominance frontiers, a phi being an assignment too
+    let mut blocks: Vec<IrBlock> =
+        succs.iter().map(|_| IrBlock { phis: Vec::new(), insts: Vec::new(), exit: IrExit::End }).collect();
+    let mut phi_vars = vec![Vec::new(); succs.len()];
+    for var in (0..vars).filter(|&var| crosses_blocks[var]) {
+        let mut has_phi = vec![false; succs.len()];
+        let mut work = assigned_in[var].clone();
+        while let Some(b) = work.pop() {
+            for &join in &frontiers[b] {
+                if !has_phi[join] {
+                    has_phi[join] = true;
+                    phi_vars[join].push(var);
+                    blocks[join].phis.push(Phi { dest: Value(usize::MAX), var: scopes.names[var], args: Vec::new() });
+                    if !assigned_in[var].contains(&join) {
+                        work.push(join);
+                    }
+                }
+            }
+        }
+    }
+
+    let mut lowerer = Lowerer {
+        interner,
+        scopes,
+        blocks,
+        phi_vars,
+        stacks: vec![Vec::new(); vars],
+        values: 0,
+    };
+    for (var, param) in params.iter().enumerate() {
+        let value = lowerer.emit(0, InstKind::Param(param.name));
+        lowerer.stacks[var].push(value);
+    }
+    lowerer.visit(0, &cfg_blocks, &new_index, &children);
+    for block in &mut lowerer.blocks {
+        for phi in &mut block.phis {
+            phi.args.sort();
+        }
+    }
+    IrFunction { name, blocks: lowerer.blocks, values: lowerer.values }
+}
+
+fn exit_targets(exit: &Exit) -> Vec<usize> {
+    match *exit {
+        Exit::Goto(to) => vec![to],
+        Exit::Branch { then, other, .. } => vec![then, other],
+        Exit::Return(_) | Exit::End => Vec::new(),
+    }
+}
+
+// the operator a compound assignment applies
+fn assign_operator(op: Token) -> Option<Token> {
+    match op {
+        Token::ADD_ASSIGN => Some(Token::ADD),
+        Token::SUB_ASSIGN => Some(Token::SUB),
+        Token::MUL_ASSIGN => Some(Token::MUL),
+        Token::DIV_ASSIGN => Some(Token::DIV),
+        _ => None,
+    }
+}
+
+struct Lowerer<'a> {
+    interner: &'a Interner,
+    scopes: Scopes,
+    blocks: Vec<IrBlock>,
+    phi_vars: Vec<Vec<usize>>, //the variable of each phi, by block
+    stacks: Vec<Vec<Value>>, //the values of each variable along the dominator tree, current one last
+    values: usize,
+}
+
+impl Lowerer<'_> {
+    fn new_value(&mut self) -> Value {
+        self.values += 1;
+        Value(self.values - 1)
+    }
+
+    fn emit(&mut self, b: usize, kind: InstKind) -> Value {
+        let dest = self.new_value();
+        self.blocks[b].insts.push(Inst { dest: Some(dest), kind });
+        dest
+    }
+
+    fn emit_void(&mut self, b: usize, kind: InstKind) {
+        self.blocks[b].insts.push(Inst { dest: None, kind });
+    }
+
+    // the current value of `var` in block `b`, undefined if nothing on the way
+    // to `b` assigned it
+    fn read(&mut self, b: usize, var: usize) -> Value {
+        if let Some(&value) = self.stacks[var].last() {
+            return value;
+        }
+        let value = self.emit(b, InstKind::Undef);
+        self.stacks[var].push(value);
+        value
+    }
+
+    // lowers block `b` and then the blocks it immediately dominates, each
+    // seeing the values of variables that reach it
+    fn visit(&mut self, b: usize, cfg_blocks: &[cfg::BasicBlock], new_index: &[usize], children: &[Vec<usize>]) {
+        let depths: Vec<usize> = self.stacks.iter().map(Vec::len).collect();
+        for k in 0..self.blocks[b].phis.len() {
+            let dest = self.new_value();
+            self.blocks[b].phis[k].dest = dest;
+            self.stacks[self.phi_vars[b][k]].push(dest);
+        }
+        for stmt in &cfg_blocks[b].stmts {
+            self.stmt(b, stmt);
+        }
+        self.blocks[b].exit = match &cfg_blocks[b].exit {
+            Exit::Goto(to) => IrExit::Goto(new_index[*to]),
+            Exit::Branch { cond, then, other } => {
+                let cond = self.expr(b, cond);
+                IrExit::Branch { cond, then: new_index[*then], other: new_index[*other] }
+            }
+            Exit::Return(value) => IrExit::Return(value.as_ref().map(|value| self.expr(b, value))),
+            Exit::End => IrExit::End,
+        };
+        let mut targets = self.blocks[b].exit.targets();
+        targets.dedup();
+        for to in targets {
+            for k in 0..self.phi_vars[to].len() {
+                let value = self.read(b, self.phi_vars[to][k]);
+                self.blocks[to].phis[k].args.push((b, value));
+            }
+        }
+        for &child in &children[b] {
+            self.visit(child, cfg_blocks, new_index, children);
+        }
+        for (stack, depth) in self.stacks.iter_mut().zip(depths) {
+            stack.truncate(depth);
+        }
+    }
+
+    fn stmt(&mut self, b: usize, stmt: &Stmt) {
+        match &stmt.kind {
+            StmtKind::Let { value, .. } => {
+                let value = match value {
+                    Some(value) => self.expr(b, value),
+                    None => self.emit(b, InstKind::Undef),
+                };
+                let var = self.scopes.declared[&stmt.span];
+                self.stacks[var].push(value);
+            }
+            StmtKind::Assign { target, op, value } => {
+                let value = self.expr(b, value);
+                let operator = assign_operator(*op);
+                match &target.kind {
+                    ExprKind::Var(name) => {
+                        let var = self.scopes.refers.get(&target.span).copied();
+                        let value = match operator {
+                            Some(op) => {
+                                let old = match var {
+                                    Some(var) => self.read(b, var),
+                                    None => self.emit(b, InstKind::Global(*name)),
+                                };
+                                self.emit(b, InstKind::Binary { op, left: old, right: value })
+                            }
+                            None => value,
+                        };
+                        match var {
+                            Some(var) => self.stacks[var].push(value),
+                            None => self.emit_void(b, InstKind::SetGlobal { name: *name, value }),
+                        }
+                    }
+                    ExprKind::Field { object, field } => {
+                        let object = self.expr(b, object);
+                        let value = match operator {
+                            Some(op) => {
+                                let old = self.emit(b, InstKind::Field { object, field: *field });
+                                self.emit(b, InstKind::Binary { op, left: old, right: value })
+                            }
+                            None => value,
+                        };
+                        self.emit_void(b, InstKind::SetField { object, field: *field, value });
+                    }
+                    // the parser only assigns to variables and fields, an AST
+                    // read from JSON that assigns to something else stores nothing
+                    _ => {
+                        self.expr(b, target);
+                    }
+                }
+            }
+            StmtKind::Print(args) => {
+                let args = args.iter().map(|arg| self.expr(b, arg)).collect();
+                self.emit_void(b, InstKind::Print(args));
+            }
+            StmtKind::Expr(expr) => {
+                self.expr(b, expr);
+            }
+            _ => unreachable!("cfg.rs ends a block at {:?}", stmt.kind),
+        }
+    }
+
+    fn expr(&mut self, b: usize, expr: &Expr) -> Value {
+        let kind = match &expr.kind {
+            ExprKind::Int(value) => InstKind::Const(Token::LIT_INT32(*value)),
+            ExprKind::Float(value) => InstKind::Const(Token::LIT_FLT32(*value)),
+            ExprKind::Char(value) => InstKind::Const(Token::LIT_CHAR(*value)),
+            ExprKind::Str(value) => InstKind::Const(Token::LIT_STRING(*value)),
+            ExprKind::Bool(value) => InstKind::Const(Token::LIT_BOOL(*value)),
+            ExprKind::Var(name) => match self.scopes.refers.get(&expr.span) {
+                Some(&var) => return self.read(b, var),
+                None => InstKind::Global(*name),
+            },
+            ExprKind::Unary { op, operand } => InstKind::Unary { op: *op, operand: self.expr(b, operand) },
+            ExprKind::Binary { op, left, right } => {
+                let left = self.expr(b, left);
+                InstKind::Binary { op: *op, left, right: self.expr(b, right) }
+            }
+            ExprKind::Call { callee, args } => {
+                let args = args.iter().map(|arg| self.expr(b, arg)).collect();
+                InstKind::Call { callee: callee.text(self.interner), args }
+            }
+            ExprKind::Field { object, field } => InstKind::Field { object: self.expr(b, object), field: *field },
+        };
+        self.emit(b, kind)
+    }
+}
+
+// ---------------------------------------------------------------------------
+// verifier
+
+// checks the SSA invariants: every block is reachable, every value is defined
+// once, by a phi or an instruction, and every use is dominated by its
+// definition. A phi has one value for each predecessor, used at the end of
+// that predecessor. Gives the first one broken
+pub fn verify(function: &IrFunction) -> Result<(), String> {
+    let succs: Vec<Vec<usize>> = function.blocks.iter().map(|block| block.exit.targets()).collect();
+    for (b, targets) in succs.iter().enumerate() {
+        if let Some(to) = targets.iter().find(|&&to| to >= succs.len()) {
+            return Err(format!("b{} jumps to b{}, which doesn't exist", b, to));
+        }
+    }
+    let idom = immediate_dominators(&succs);
+    if let Some(b) = idom.iter().position(Option::is_none) {
+        return Err(format!("b{} can't be reached from the entry", b));
+    }
+    let preds = predecessors(&succs);
+
+    // the block and position each value is defined at, phis at 0 and
+    // instructions from 1
+    let mut defs = HashMap::new();
+    for (b, block) in function.blocks.iter().enumerate() {
+        let dests = block.phis.iter().map(|phi| (phi.dest, 0));
+        let dests = dests.chain(block.insts.iter().enumerate().filter_map(|(i, inst)| Some((inst.dest?, i + 1))));
+        for (dest, pos) in dests {
+            if defs.insert(dest, (b, pos)).is_some() {
+                return Err(format!("{} is defined more than once", dest.text()));
+            }
+        }
+    }
+    let check_use = |value: Value, b: usize, pos: usize| match defs.get(&value) {
+        None => Err(format!("{} is used in b{} but never defined", value.text(), b)),
+        Some(&(def, def_pos)) if (def == b && def_pos < pos) || (def != b && dominates(&idom, def, b)) => Ok(()),
+        Some(&(def, _)) => {
+            Err(format!("{} is used in b{} where its definition in b{} doesn't dominate", value.text(), b, def))
+        }
+    };
+
+    for (b, block) in function.blocks.iter().enumerate() {
+        for phi in &block.phis {
+            let mut from: Vec<usize> = phi.args.iter().map(|&(p, _)| p).collect();
+            let mut expected = preds[b].clone();
+            from.sort();
+            expected.sort();
+            if from != expected {
+                return Err(format!(
+                    "phi {} in b{} has values from [{}] but the block's predecessors are [{}]",
+                    phi.dest.text(),
+                    b,
+                    block_list(&from),
+                    block_list(&expected)
+                ));
+            }
+            for &(p, value) in &phi.args {
+                check_use(value, p, usize::MAX)?;
+            }
+        }
+        for (i, inst) in block.insts.iter().enumerate() {
+            for value in inst.kind.operands() {
+                check_use(value, b, i + 1)?;
+            }
+        }
+        if let Some(value) = block.exit.operand() {
+            check_use(value, b, usize::MAX)?;
+        }
+    }
+    Ok(())
+}
+
+fn block_list(blocks: &[usize]) -> String {
+    blocks.iter().map(|b| format!("b{}", b)).collect::<Vec<_>>().join(", ")
+}
+
+// ---------------------------------------------------------------------------
+// text
+
+// what `parse --emit ir` prints, e.g.
+//     func count
+//     b1: ; preds b0, b2
+//         %2 = phi [b0 %1, b2 %5] ; i
+//         %3 = %2 < %0
+//         if %3 then b2 else b3
+pub fn program_text(functions: &[IrFunction], interner: &Interner) -> String {
+    let texts: Vec<String> = functions.iter().map(|function| function_text(function, interner)).collect();
+    texts.join("\n")
+}
+
+pub fn function_text(function: &IrFunction, interner: &Interner) -> String {
+    let succs: Vec<Vec<usize>> = function.blocks.iter().map(|block| block.exit.targets()).collect();
+    let preds = predecessors(&succs);
+    let mut text = format!("func {}\n", interner.get(function.name));
+    for (b, block) in function.blocks.iter().enumerate() {
+        let mut from = preds[b].clone();
+        from.sort();
+        match from.is_empty() {
+            true => text.push_str(&format!("b{}:\n", b)),
+            false => text.push_str(&format!("b{}: ; preds {}\n", b, block_list(&from))),
+        }
+        for phi in &block.phis {
+            let args: Vec<String> = phi.args.iter().map(|&(p, value)| format!("b{} {}", p, value.text())).collect();
+            let var = interner.get(phi.var);
+            text.push_str(&format!("    {} = phi [{}] ; {}\n", phi.dest.text(), args.join(", "), var));
+        }
+        for inst in &block.insts {
+            let kind = inst_text(&inst.kind, interner);
+            match inst.dest {
+                Some(dest) => text.push_str(&format!("    {} = {}\n", dest.text(), kind)),
+                None => text.push_str(&format!("    {}\n", kind)),
+            }
+        }
+        let exit = match &block.exit {
+            IrExit::Goto(to) => format!("goto b{}", to),
+            IrExit::Branch { cond, then, other } => format!("if {} then b{} else b{}", cond.text(), then, other),
+            IrExit::Return(Some(value)) => format!("return {}", value.text()),
+            IrExit::Return(None) => "return".to_string(),
+            IrExit::End => "end".to_string(),
+        };
+        text.push_str(&format!("    {}\n", exit));
+    }
+    text
+}
+
+fn inst_text(kind: &InstKind, interner: &Interner) -> String {
+    let list = |values: &[Value]| values.iter().map(|value| value.text()).collect::<Vec<_>>().join(", ");
+    match kind {
+        InstKind::Const(token) => unlex::spelling(token, interner),
+        InstKind::Param(name) => format!("param {}", interner.get(*name)),
+        InstKind::Undef => "undef".to_string(),
+        InstKind::Global(name) => format!("global {}", interner.get(*name)),
+        InstKind::Unary { op, operand } => format!("{}{}", unlex::spelling(op, interner), operand.text()),
+        InstKind::Binary { op, left, right } => {
+            format!("{} {} {}", left.text(), unlex::spelling(op, interner), right.text())
+        }
+        InstKind::Call { callee, args } => format!("call {}({})", callee, list(args)),
+        InstKind::Field { object, field } => format!("{}.{}", object.text(), interner.get(*field)),
+        InstKind::SetGlobal { name, value } => format!("global {} = {}", interner.get(*name), value.text()),
+        InstKind::SetField { object, field, value } => {
+            format!("{}.{} = {}", object.text(), interner.get(*field), value.text())
+        }
+        InstKind::Print(args) => format!("print {}", list(args)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::LexerConfig;
+    use crate::fuzz::Rng;
+    use crate::parser::parse;
+    use crate::source::FileId;
+
+    fn lower(input: &str) -> (Vec<IrFunction>, Interner) {
+        let mut interner = Interner::new();
+        let (program, errors) = parse(input, FileId::default(), &LexerConfig::default(), &mut interner);
+        assert_eq!(errors, vec![], "{}", input);
+        (lower_program(&program, &interner), interner)
+    }
+
+    const COUNT: &str = "func count(n : int32) -> int32 [
+        let i = 0;
+        let total = 0;
+        while i < n [
+            if i % 2 == 0 [ total += i; ] else [ print i; ]
+            i += 1;
+        ]
+        return total;
+    ]";
+
+    #[test]
+    fn variables_merged_at_joins_get_phis() {
+        let (functions, interner) = lower(COUNT);
+        assert_eq!(verify(&functions[0]), Ok(()));
+        assert_eq!(
+            function_text(&functions[0], &interner),
+            "func count
+b0:
+    %0 = param n
+    %1 = 0
+    %2 = 0
+    goto b1
+b1: ; preds b0, b5
+    %3 = phi [b0 %1, b5 %13] ; i
+    %4 = phi [b0 %2, b5 %11] ; total
+    %5 = %3 < %0
+    if %5 then b2 else b6
+b2: ; preds b1
+    %6 = 2
+    %7 = %3 % %6
+    %8 = 0
+    %9 = %7 == %8
+    if %9 then b3 else b4
+b3: ; preds b2
+    %10 = %4 + %3
+    goto b5
+b4: ; preds b2
+    print %3
+    goto b5
+b5: ; preds b3, b4
+    %11 = phi [b3 %10, b4 %4] ; total
+    %12 = 1
+    %13 = %3 + %12
+    goto b1
+b6: ; preds b1
+    return %4
+"
+        );
+    }
+
+    #[test]
+    fn scopes_globals_and_unassigned_variables() {
+        let (functions, interner) = lower(
+            "func f(x : int32) [
+                let y;
+                if x > 0 [ let x = 1; y = x; p.f = x; ]
+                print x, y, g;
+                g = add(x, 2);
+            ]",
+        );
+        assert_eq!(verify(&functions[0]), Ok(()));
+        assert_eq!(
+            function_text(&functions[0], &interner),
+            "func f
+b0:
+    %0 = param x
+    %1 = undef
+    %2 = 0
+    %3 = %0 > %2
+    if %3 then b1 else b2
+b1: ; preds b0
+    %4 = 1
+    %5 = global p
+    %5.f = %4
+    goto b2
+b2: ; preds b0, b1
+    %6 = phi [b0 %1, b1 %4] ; y
+    %7 = global g
+    print %0, %6, %7
+    %8 = 2
+    %9 = call add(%0, %8)
+    global g = %9
+    end
+"
+        );
+    }
+
+    fn random_expr(rng: &mut Rng) -> String {
+        let names = ["a", "b", "c"];
+        match rng.below(3) {
+            0 => rng.below(10).to_string(),
+            1 => names[rng.below(3)].to_string(),
+            _ => format!("{} + {}", names[rng.below(3)], rng.below(10)),
+        }
+    }
+
+    fn random_block(rng: &mut Rng, depth: usize) -> String {
+        let mut text = String::from("[ ");
+        for _ in 0..rng.below(5) {
+            let name = ["a", "b", "c"][rng.below(3)];
+            let stmt = match rng.below(if depth < 3 { 9 } else { 5 }) {
+                0 => format!("let {} = {};", name, random_expr(rng)),
+                1 => format!("{} = {};", name, random_expr(rng)),
+                2 => format!("{} += {};", name, random_expr(rng)),
+                3 => format!("print {};", random_expr(rng)),
+                4 if rng.below(4) == 0 => format!("return {};", random_expr(rng)),
+                4 => format!("let {};", name),
+                5 => format!("while {} < 5 {}", name, random_block(rng, depth + 1)),
+                6 => format!("if {} {}", random_expr(rng), random_block(rng, depth + 1)),
+                7 => {
+                    let (then, other) = (random_block(rng, depth + 1), random_block(rng, depth + 1));
+                    format!("if {} {} else {}", random_expr(rng), then, other)
+                }
+                _ => random_block(rng, depth + 1),
+            };
+            text.push_str(&stmt);
+            text.push(' ');
+        }
+        text.push(']');
+        text
+    }
+
+    #[test]
+    fn random_programs_are_valid_ssa() {
+        let mut rng = Rng(0x2545F4914F6CDD1D);
+        let mut phis = 0;
+        for _ in 0..300 {
+            let input = format!("func f(a : int32) {}", random_block(&mut rng, 0));
+            let (functions, interner) = lower(&input);
+            let function = &functions[0];
+            let text = function_text(function, &interner);
+            assert_eq!(verify(function), Ok(()), "{}\n{}", input, text);
+            phis += function.blocks.iter().map(|block| block.phis.len()).sum::<usize>();
+        }
+        assert!(phis > 100, "only {} phis", phis);
+    }
+
+    #[test]
+    fn broken_ssa_is_reported() {
+        let (functions, _) = lower(COUNT);
+        let valid = &functions[0];
+
+        let mut function = valid.clone();
+        function.blocks[5].phis[0].args.pop();
+        let error = "phi %11 in b5 has values from [b3] but the block's predecessors are [b3, b4]";
+        assert_eq!(verify(&function), Err(error.to_string()));
+
+        // %10 is only defined on the path through b3
+        let mut function = valid.clone();
+        function.blocks[6].exit = IrExit::Return(Some(Value(10)));
+        assert_eq!(verify(&function), Err("%10 is used in b6 where its definition in b3 doesn't dominate".to_string()));
+
+        let mut function = valid.clone();
+        function.blocks[2].insts.swap(0, 1);
+        assert_eq!(verify(&function), Err("%6 is used in b2 where its definition in b2 doesn't dominate".to_string()));
+
+        let mut function = valid.clone();
+        function.blocks[3].insts[0].dest = Some(Value(1));
+        assert_eq!(verify(&function), Err("%1 is defined more than once".to_string()));
+
+        let mut function = valid.clone();
+        function.blocks[1].exit = IrExit::Goto(6);
+        assert_eq!(verify(&function), Err("b2 can't be reached from the entry".to_string()));
+    }
+}
@@ -0,0 +1,295 @@
+// Minimal JSON reader and writer, enough for dialect files and AST dumps
+// without pulling in serde.
+use std::iter::Peekable;
+use std::str::CharIndices;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Json {
+    Null,
+    Bool(bool),
+    Number(f64),
+    Str(String),
+    Array(Vec<Json>),
+    Object(Vec<(String, Json)>), //keeps the order keys were written in
+}
+
+impl Json {
+    pub fn get(&self, key: &str) -> Option<&Json> {
+        match self {
+            Json::Object(fields) => fields.iter().find(|(k, _)| k == key).map(|(_, v)| v),
+            _ => None,
+        }
+    }
+}
+
+// pretty printed with two-space indents; arrays and objects holding only
+// numbers, strings and the like stay on one line
+pub fn write(value: &Json) -> String {
+    let mut out = String::new();
+    write_value(value, 0, &mut out);
+    out.push('\n');
+    out
+}
+
+fn write_value(value: &Json, indent: usize, out: &mut String) {
+    let nested = |v: &Json| matches!(v, Json::Array(items) if !items.is_empty())
+        || matches!(v, Json::Object(fields) if !fields.is_empty());
+    match value {
+        Json::Null => out.push_str("null"),
+        Json::Bool(b) => out.push_str(&b.to_string()),
+        // JSON has no spelling for infinity or NaN
+        Json::Number(n) if !n.is_finite() => out.push_str("null"),
+        Json::Number(n) => out.push_str(&n.to_string()),
+        Json::Str(s) => write_string(s, out),
+        Json::Array(items) => {
+            let inline = !items.iter().any(nested);
+            out.push('[');
+            for (i, item) in items.iter().enumerate() {
+                out.push_str(if i > 0 { "," } else { "" });
+                if inline {
+                    out.push_str(if i > 0 { " " } else { "" });
+                } else {
+                    newline(indent + 1, out);
+                }
+                write_value(item, indent + 1, out);
+            }
+            if !inline {
+                newline(indent, out);
+            }
+            out.push(']');
+        }
+        Json::Object(fields) => {
+            let inline = !fields.iter().any(|(_, v)| nested(v));
+            out.push('{');
+            for (i, (key, field)) in fields.iter().enumerate() {
+                out.push_str(if i > 0 { "," } else { "" });
+                if inline {
+                    out.push_str(if i > 0 { " " } else { "" });
+                } else {
+                    newline(indent + 1, out);
+                }
+                write_string(key, out);
+                out.push_str(": ");
+                write_value(field, indent + 1, out);
+            }
+            if !inline {
+                newline(indent, out);
+            }
+            out.push('}');
+        }
+    }
+}
+
+fn newline(indent: usize, out: &mut String) {
+    out.push('\n');
+    out.push_str(&"  ".repeat(indent));
+}
+
+fn write_string(s: &str, out: &mut String) {
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\t' => out.push_str("\\t"),
+            '\r' => out.push_str("\\r"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+}
+
+pub fn parse(text: &str) -> Result<Json, String> {
+    let mut parser = JsonParser { chars: text.char_indices().peekable(), len: text.len() };
+    let value = parser.value()?;
+    parser.skip_whitespace();
+    match parser.chars.peek() {
+        None => Ok(value),
+        Some(&(pos, c)) => Err(format!("Unexpected '{}' after JSON value at position {}", c, pos)),
+    }
+}
+
+struct JsonParser<'a> {
+    chars: Peekable<CharIndices<'a>>,
+    len: usize,
+}
+
+impl JsonParser<'_> {
+    fn pos(&mut self) -> usize {
+        self.chars.peek().map(|&(pos, _)| pos).unwrap_or(self.len)
+    }
+
+    fn skip_whitespace(&mut self) {
+        while let Some(&(_, c)) = self.chars.peek() {
+            if c.is_whitespace() {
+                self.chars.next();
+            } else {
+                break;
+            }
+        }
+    }
+
+    fn expect(&mut self, expected: char) -> Result<(), String> {
+        self.skip_whitespace();
+        let pos = self.pos();
+        match self.chars.next() {
+            Some((_, c)) if c == expected => Ok(()),
+            Some((_, c)) => Err(format!("Expected '{}' but found '{}' at position {}", expected, c, pos)),
+            None => Err(format!("Expected '{}' but the input ended", expected)),
+        }
+    }
+
+    fn word(&mut self, word: &str, value: Json) -> Result<Json, String> {
+        for expected in word.chars() {
+            let pos = self.pos();
+            match self.chars.next() {
+                Some((_, c)) if c == expected => {}
+                _ => return Err(format!("Invalid literal at position {}", pos)),
+            }
+        }
+        Ok(value)
+    }
+
+    fn value(&mut self) -> Result<Json, String> {
+        self.skip_whitespace();
+        let pos = self.pos();
+        match self.chars.peek().map(|&(_, c)| c) {
+            None => Err("Unexpected end of JSON input".to_string()),
+            Some('n') => self.word("null", Json::Null),
+            Some('t') => self.word("true", Json::Bool(true)),
+            Some('f') => self.word("false", Json::Bool(false)),
+            Some('"') => Ok(Json::Str(self.string()?)),
+            Some('[') => {
+                self.chars.next();
+                let mut items = Vec::new();
+                self.skip_whitespace();
+                if self.chars.peek().map(|&(_, c)| c) == Some(']') {
+                    self.chars.next();
+                    return Ok(Json::Array(items));
+                }
+                loop {
+                    items.push(self.value()?);
+                    self.skip_whitespace();
+                    if self.chars.peek().map(|&(_, c)| c) == Some(',') {
+                        self.chars.next();
+                    } else {
+                        self.expect(']')?;
+                        return Ok(Json::Array(items));
+                    }
+                }
+            }
+            Some('{') => {
+                self.chars.next();
+                let mut fields = Vec::new();
+                self.skip_whitespace();
+                if self.chars.peek().map(|&(_, c)| c) == Some('}') {
+                    self.chars.next();
+                    return Ok(Json::Object(fields));
+                }
+                loop {
+                    self.skip_whitespace();
+                    let key = self.string()?;
+                    self.expect(':')?;
+                    fields.push((key, self.value()?));
+                    self.skip_whitespace();
+                    if self.chars.peek().map(|&(_, c)| c) == Some(',') {
+                        self.chars.next();
+                    } else {
+                        self.expect('}')?;
+                        return Ok(Json::Object(fields));
+                    }
+                }
+            }
+            Some(c) if c == '-' || c.is_ascii_digit() => {
+                let mut number = String::new();
+                while let Some(&(_, c)) = self.chars.peek() {
+                    if c.is_ascii_digit() || matches!(c, '-' | '+' | '.' | 'e' | 'E') {
+                        number.push(c);
+                        self.chars.next();
+                    } else {
+                        break;
+                    }
+                }
+                number
+                    .parse::<f64>()
+                    .map(Json::Number)
+                    .map_err(|_| format!("Invalid number '{}' at position {}", number, pos))
+            }
+            Some(c) => Err(format!("Unexpected '{}' at position {}", c, pos)),
+        }
+    }
+
+    fn string(&mut self) -> Result<String, String> {
+        self.expect('"')?;
+        let mut result = String::new();
+        loop {
+            let pos = self.pos();
+            match self.chars.next() {
+                None => return Err("Unterminated JSON string".to_string()),
+                Some((_, '"')) => return Ok(result),
+                Some((_, '\\')) => {
+                    let escaped = match self.chars.next() {
+                        Some((_, c)) => c,
+                        None => return Err("Unterminated JSON string".to_string()),
+                    };
+                    match escaped {
+                        'n' => result.push('\n'),
+                        't' => result.push('\t'),
+                        'r' => result.push('\r'),
+                        'b' => result.push('\u{8}'),
+                        'f' => result.push('\u{c}'),
+                        '/' | '\\' | '"' => result.push(escaped),
+                        'u' => result.push(self.unicode_escape(pos)?),
+                        _ => return Err(format!("Invalid escape '\\{}' at position {}", escaped, pos)),
+                    }
+                }
+                Some((_, c)) => result.push(c),
+            }
+        }
+    }
+
+    // \uXXXX, with a second \uXXXX for characters outside the basic plane
+    fn unicode_escape(&mut self, pos: usize) -> Result<char, String> {
+        let high = self.hex4(pos)?;
+        if (0xD800..0xDC00).contains(&high) {
+            // no whitespace is allowed between the two halves, so don't use expect
+            if !matches!(self.chars.next(), Some((_, '\\'))) || !matches!(self.chars.next(), Some((_, 'u'))) {
+                return Err(format!("Invalid surrogate pair at position {}", pos));
+            }
+            let low = self.hex4(pos)?;
+            if !(0xDC00..0xE000).contains(&low) {
+                return Err(format!("Invalid surrogate pair at position {}", pos));
+            }
+            let value = 0x10000 + ((high - 0xD800) << 10) + (low - 0xDC00);
+            return char::from_u32(value).ok_or(format!("Invalid surrogate pair at position {}", pos));
+        }
+        char::from_u32(high).ok_or(format!("Invalid unicode escape at position {}", pos))
+    }
+
+    fn hex4(&mut self, pos: usize) -> Result<u32, String> {
+        let mut digits = String::new();
+        for _ in 0..4 {
+            match self.chars.next() {
+                Some((_, c)) if c.is_ascii_hexdigit() => digits.push(c),
+                _ => return Err(format!("Invalid unicode escape at position {}", pos)),
+            }
+        }
+        Ok(u32::from_str_radix(&digits, 16).unwrap())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unicode_escapes_and_surrogate_pairs() {
+        assert_eq!(parse(r#""\u00e9""#), Ok(Json::Str("\u{e9}".to_string())));
+        assert_eq!(parse(r#""\ud83d\ude00""#), Ok(Json::Str("\u{1F600}".to_string())));
+        assert!(parse(r#""\ud83d \ude00""#).is_err());
+        assert!(parse(r#""\ud83d\u0041""#).is_err());
+        assert!(parse(r#""\ud83dx""#).is_err());
+    }
+}
@@ -0,0 +1,790 @@
+// Lexer generator: token definitions written as regular expressions with
+// priorities are turned into Thompson NFAs, combined, converted to a DFA by
+// subset construction, minimised with Hopcroft's algorithm and run with
+// maximal munch. tokens.spec describes the same language as the hand-written
+// Lexer.
+use crate::intern::Interner;
+use crate::source::FileId;
+use crate::{LexError, Span, SpannedToken, Token, TokenKind, read_escape};
+use std::collections::{BTreeSet, HashMap};
+use std::ops::Range;
+
+pub const DEFAULT_SPEC: &str = include_str!("tokens.spec");
+
+const MAX_CHAR: u32 = char::MAX as u32;
+
+// ---------------------------------------------------------------------------
+// token specs
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TokenRule {
+    pub name: String,
+    pub priority: u32,
+    pub pattern: String,
+    pub token: Option<Token>, //the token a match makes, for rules whose token has no payload
+}
+
+pub fn parse_spec(spec: &str) -> Result<Vec<TokenRule>, String> {
+    let fixed = fixed_tokens();
+    let mut rules = Vec::new();
+    for (number, line) in spec.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let mut fields = line.splitn(2, char::is_whitespace);
+        let name = fields.next().unwrap().to_string();
+        let rest = fields.next().unwrap_or("").trim_start();
+        let mut fields = rest.splitn(2, char::is_whitespace);
+        let priority = fields
+            .next()
+            .unwrap_or("")
+            .parse::<u32>()
+            .map_err(|_| format!("line {}: expected NAME PRIORITY PATTERN", number + 1))?;
+        let pattern = fields.next().unwrap_or("").trim().to_string();
+        if pattern.is_empty() {
+            return Err(format!("line {}: missing pattern for {}", number + 1, name));
+        }
+        let token = fixed.get(&name).copied();
+        if name != "skip" && !matches!(name.as_str(), "ID" | "LIT_INT32" | "LIT_FLT32" | "LIT_CHAR" | "LIT_STRING" | "LIT_BOOL")
+            && token.is_none()
+        {
+            return Err(format!("line {}: unknown token {}", number + 1, name));
+        }
+        rules.push(TokenRule { name, priority, pattern, token });
+    }
+    Ok(rules)
+}
+
+// tokens without a payload by their Debug name, looked up once per spec
+fn fixed_tokens() -> HashMap<String, Token> {
+    TokenKind::ALL.into_iter().filter_map(|kind| Some((format!("{:?}", kind), kind.token()?))).collect()
+}
+
+// ---------------------------------------------------------------------------
+// regular expressions
+
+// a set of chars as sorted, non-overlapping inclusive ranges
+type CharSet = Vec<(u32, u32)>;
+
+#[derive(Debug, Clone, PartialEq)]
+enum Regex {
+    Empty,
+    Chars(CharSet),
+    Concat(Vec<Regex>),
+    Alt(Vec<Regex>),
+    Star(Box<Regex>),
+    Repeat(Box<Regex>, u32, Option<u32>), //min and optional max
+}
+
+fn normalize(mut ranges: CharSet) -> CharSet {
+    ranges.sort();
+    let mut merged: CharSet = Vec::new();
+    for (lo, hi) in ranges {
+        match merged.last_mut() {
+            Some(last) if lo <= last.1.saturating_add(1) => last.1 = last.1.max(hi),
+            _ => merged.push((lo, hi)),
+        }
+    }
+    merged
+}
+
+fn complement(set: &CharSet) -> CharSet {
+    let mut result = Vec::new();
+    let mut next = 0;
+    for &(lo, hi) in set {
+        if lo > next {
+            result.push((next, lo - 1));
+        }
+        next = hi + 1;
+    }
+    if next <= MAX_CHAR {
+        result.push((next, MAX_CHAR));
+    }
+    result
+}
+
+// the chars char::is_whitespace accepts, which is what the Lexer skips
+fn whitespace() -> CharSet {
+    let mut ranges = Vec::new();
+    for c in (0..=0x3000u32).filter_map(char::from_u32) {
+        if c.is_whitespace() {
+            ranges.push((c as u32, c as u32));
+        }
+    }
+    normalize(ranges)
+}
+
+struct RegexParser<'a> {
+    chars: Vec<char>,
+    pos: usize,
+    pattern: &'a str,
+}
+
+impl RegexParser<'_> {
+    fn parse(pattern: &str) -> Result<Regex, String> {
+        let mut parser = RegexParser { chars: pattern.chars().collect(), pos: 0, pattern };
+        let regex = parser.alternation()?;
+        if parser.pos < parser.chars.len() {
+            return Err(parser.error("unexpected ')'"));
+        }
+        Ok(regex)
+    }
+
+    fn error(&self, message: &str) -> String {
+        format!("{} at {} in /{}/", message, self.pos, self.pattern)
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    fn next(&mut self) -> Option<char> {
+        let c = self.peek();
+        self.pos += 1;
+        c
+    }
+
+    fn alternation(&mut self) -> Result<Regex, String> {
+        let mut options = vec![self.concatenation()?];
+        while self.peek() == Some('|') {
+            self.pos += 1;
+            options.push(self.concatenation()?);
+        }
+        Ok(if options.len() == 1 { options.pop().unwrap() } else { Regex::Alt(options) })
+    }
+
+    fn concatenation(&mut self) -> Result<Regex, String> {
+        let mut parts = Vec::new();
+        while let Some(c) = self.peek() {
+            if c == '|' || c == ')' {
+                break;
+            }
+            parts.push(self.repetition()?);
+        }
+        Ok(match parts.len() {
+            0 => Regex::Empty,
+            1 => parts.pop().unwrap(),
+            _ => Regex::Concat(parts),
+        })
+    }
+
+    fn repetition(&mut self) -> Result<Regex, String> {
+        let mut regex = self.atom()?;
+        loop {
+            regex = match self.peek() {
+                Some('*') => Regex::Star(Box::new(regex)),
+                Some('+') => Regex::Repeat(Box::new(regex), 1, None),
+                Some('?') => Regex::Repeat(Box::new(regex), 0, Some(1)),
+                Some('{') => {
+                    self.pos += 1;
+                    let min = self.number()?;
+                    let max = if self.peek() == Some(',') {
+                        self.pos += 1;
+                        if self.peek() == Some('}') { None } else { Some(self.number()?) }
+                    } else {
+                        Some(min)
+                    };
+                    if self.peek() != Some('}') {
+                        return Err(self.error("expected '}'"));
+                    }
+                    if max.is_some_and(|max| max < min) {
+                        return Err(self.error("repeat maximum is below its minimum"));
+                    }
+                    Regex::Repeat(Box::new(regex), min, max)
+                }
+                _ => return Ok(regex),
+            };
+            self.pos += 1;
+        }
+    }
+
+    fn number(&mut self) -> Result<u32, String> {
+        let start = self.pos;
+        while self.peek().is_some_and(|c| c.is_ascii_digit()) {
+            self.pos += 1;
+        }
+        let digits: String = self.chars[start..self.pos].iter().collect();
+        digits.parse().map_err(|_| self.error("expected a number"))
+    }
+
+    fn atom(&mut self) -> Result<Regex, String> {
+        match self.next() {
+            None => Err(self.error("unexpected end of pattern")),
+            Some('(') => {
+                let inner = self.alternation()?;
+                if self.next() != Some(')') {
+                    return Err(self.error("expected ')'"));
+                }
+                Ok(inner)
+            }
+            Some('[') => self.class(),
+            Some('.') => Ok(Regex::Chars(vec![(0, MAX_CHAR)])),
+            Some('\\') => Ok(Regex::Chars(self.escape()?)),
+            Some(c @ ('*' | '+' | '?' | '{')) => Err(self.error(&format!("nothing to repeat with '{}'", c))),
+            Some(c) => Ok(Regex::Chars(vec![(c as u32, c as u32)])),
+        }
+    }
+
+    // after a backslash
+    fn escape(&mut self) -> Result<CharSet, String> {
+        let c = match self.next() {
+            None => return Err(self.error("pattern ends in '\\'")),
+            Some('s') => return Ok(whitespace()),
+            Some('n') => '\n',
+            Some('t') => '\t',
+            Some('r') => '\r',
+            Some('0') => '\0',
+            Some(c) => c,
+        };
+        Ok(vec![(c as u32, c as u32)])
+    }
+
+    fn class(&mut self) -> Result<Regex, String> {
+        let negated = self.peek() == Some('^');
+        if negated {
+            self.pos += 1;
+        }
+        let mut ranges = Vec::new();
+        loop {
+            let lo = match self.next() {
+                None => return Err(self.error("unterminated character class")),
+                Some(']') => break,
+                Some('\\') => self.escape()?,
+                Some(c) => vec![(c as u32, c as u32)],
+            };
+            if self.peek() == Some('-') && self.chars.get(self.pos + 1).is_some_and(|&c| c != ']') {
+                self.pos += 1;
+                let hi = match self.next() {
+                    Some('\\') => self.escape()?,
+                    Some(c) => vec![(c as u32, c as u32)],
+                    None => return Err(self.error("unterminated character class")),
+                };
+                if lo.len() != 1 || hi.len() != 1 || lo[0].0 > hi[0].0 {
+                    return Err(self.error("invalid range in character class"));
+                }
+                ranges.push((lo[0].0, hi[0].0));
+            } else {
+                ranges.extend(lo);
+            }
+        }
+        let ranges = normalize(ranges);
+        Ok(Regex::Chars(if negated { complement(&ranges) } else { ranges }))
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Thompson NFA
+
+#[derive(Debug, Default)]
+struct NfaState {
+    epsilon: Vec<usize>,
+    moves: Vec<(u32, u32, usize)>,
+    accept: Option<usize>, //rule index
+}
+
+#[derive(Debug, Default)]
+struct Nfa {
+    states: Vec<NfaState>,
+}
+
+impl Nfa {
+    fn add_state(&mut self) -> usize {
+        self.states.push(NfaState::default());
+        self.states.len() - 1
+    }
+
+    // builds a fragment for `regex` and returns its (start, end) states
+    fn build(&mut self, regex: &Regex) -> (usize, usize) {
+        match regex {
+            Regex::Empty => {
+                let s = self.add_state();
+                (s, s)
+            }
+            Regex::Chars(set) => {
+                let start = self.add_state();
+                let end = self.add_state();
+                for &(lo, hi) in set {
+                    self.states[start].moves.push((lo, hi, end));
+                }
+                (start, end)
+            }
+            Regex::Concat(parts) => {
+                let (start, mut end) = self.build(&parts[0]);
+                for part in &parts[1..] {
+                    let (s, e) = self.build(part);
+                    self.states[end].epsilon.push(s);
+                    end = e;
+                }
+                (start, end)
+            }
+            Regex::Alt(options) => {
+                let start = self.add_state();
+                let end = self.add_state();
+                for option in options {
+                    let (s, e) = self.build(option);
+                    self.states[start].epsilon.push(s);
+                    self.states[e].epsilon.push(end);
+                }
+                (start, end)
+            }
+            Regex::Star(inner) => {
+                let start = self.add_state();
+                let end = self.add_state();
+                let (s, e) = self.build(inner);
+                self.states[start].epsilon.extend([s, end]);
+                self.states[e].epsilon.extend([s, end]);
+                (start, end)
+            }
+            Regex::Repeat(inner, min, max) => {
+                // expanded into `min` copies followed by the optional ones
+                let mut parts: Vec<Regex> = (0..*min).map(|_| (**inner).clone()).collect();
+                match max {
+                    None => parts.push(Regex::Star(inner.clone())),
+                    Some(max) => {
+                        for _ in *min..*max {
+                            parts.push(Regex::Alt(vec![(**inner).clone(), Regex::Empty]));
+                        }
+                    }
+                }
+                if parts.is_empty() {
+                    return self.build(&Regex::Empty);
+                }
+                self.build(&Regex::Concat(parts))
+            }
+        }
+    }
+
+    fn closure(&self, states: impl IntoIterator<Item = usize>) -> BTreeSet<usize> {
+        let mut set = BTreeSet::new();
+        let mut stack: Vec<usize> = states.into_iter().collect();
+        while let Some(s) = stack.pop() {
+            if set.insert(s) {
+                stack.extend(&self.states[s].epsilon);
+            }
+        }
+        set
+    }
+}
+
+// ---------------------------------------------------------------------------
+// DFA
+
+// a complete DFA over alphabet classes, state 0 is the dead state
+#[derive(Debug, Clone, PartialEq)]
+pub struct Dfa {
+    class_starts: Vec<u32>, //class i covers class_starts[i]..class_starts[i + 1]
+    table: Vec<Vec<usize>>, //table[state][class]
+    accept: Vec<Option<usize>>,
+    start: usize,
+}
+
+const DEAD: usize = 0;
+
+impl Dfa {
+    pub fn state_count(&self) -> usize {
+        self.table.len()
+    }
+
+    fn class_of(&self, c: char) -> usize {
+        self.class_starts.partition_point(|&start| start <= c as u32) - 1
+    }
+
+    fn step(&self, state: usize, c: char) -> usize {
+        self.table[state][self.class_of(c)]
+    }
+
+    // the char `state` has to see next, if it can only go on with one
+    fn only_next(&self, state: usize) -> Option<char> {
+        let mut live = (0..self.class_starts.len()).filter(|&class| self.table[state][class] != DEAD);
+        let class = live.next()?;
+        let end = self.class_starts.get(class + 1).copied().unwrap_or(MAX_CHAR + 1);
+        if live.next().is_some() || end != self.class_starts[class] + 1 {
+            return None;
+        }
+        char::from_u32(self.class_starts[class])
+    }
+
+    // subset construction; `rules` ranks competing accepts
+    fn from_nfa(nfa: &Nfa, start: usize, rules: &[TokenRule]) -> Dfa {
+        // the alphabet is cut at every range boundary any NFA move uses
+        let mut cuts = BTreeSet::from([0u32]);
+        for state in &nfa.states {
+            for &(lo, hi, _) in &state.moves {
+                cuts.insert(lo);
+                if hi < MAX_CHAR {
+                    cuts.insert(hi + 1);
+                }
+            }
+        }
+        let class_starts: Vec<u32> = cuts.into_iter().collect();
+
+        let mut ids: HashMap<BTreeSet<usize>, usize> = HashMap::new();
+        let mut sets = vec![BTreeSet::new()];
+        ids.insert(BTreeSet::new(), DEAD);
+        let start_set = nfa.closure([start]);
+        ids.insert(start_set.clone(), 1);
+        sets.push(start_set);
+
+        let mut table = Vec::new();
+        let mut accept = Vec::new();
+        let mut next = 0;
+        while next < sets.len() {
+            let set = sets[next].clone();
+            let mut row = Vec::with_capacity(class_starts.len());
+            for &class_start in &class_starts {
+                let targets = set.iter().flat_map(|&s| {
+                    nfa.states[s]
+                        .moves
+                        .iter()
+                        .filter(move |&&(lo, hi, _)| lo <= class_start && class_start <= hi)
+                        .map(|&(_, _, to)| to)
+                });
+                let target = nfa.closure(targets);
+                let id = match ids.get(&target) {
+                    Some(&id) => id,
+                    None => {
+                        ids.insert(target.clone(), sets.len());
+                        sets.push(target);
+                        sets.len() - 1
+                    }
+                };
+                row.push(id);
+            }
+            table.push(row);
+            // highest priority wins, then the rule written first
+            accept.push(
+                set.iter()
+                    .filter_map(|&s| nfa.states[s].accept)
+                    .min_by_key(|&rule| (std::cmp::Reverse(rules[rule].priority), rule)),
+            );
+            next += 1;
+        }
+        Dfa { class_starts, table, accept, start: 1 }
+    }
+
+    // Hopcroft's partition refinement
+    fn minimize(&self) -> Dfa {
+        let states = self.table.len();
+        let classes = self.class_starts.len();
+
+        // inverse[class][state] = states that move to `state` on `class`
+        let mut inverse = vec![vec![Vec::new(); states]; classes];
+        for (from, row) in self.table.iter().enumerate() {
+            for (class, &to) in row.iter().enumerate() {
+                inverse[class][to].push(from);
+            }
+        }
+
+        // start with one block per accepted rule plus one for non-accepting states
+        let mut block_ids: HashMap<Option<usize>, usize> = HashMap::new();
+        let mut block_of = vec![0; states];
+        let mut blocks: Vec<Vec<usize>> = Vec::new();
+        for (state, &accepted) in self.accept.iter().enumerate() {
+            let id = *block_ids.entry(accepted).or_insert_with(|| {
+                blocks.push(Vec::new());
+                blocks.len() - 1
+            });
+            block_of[state] = id;
+            blocks[id].push(state);
+        }
+
+        let mut in_worklist = vec![true; blocks.len()];
+        let mut worklist: Vec<usize> = (0..blocks.len()).collect();
+        while let Some(splitter) = worklist.pop() {
+            in_worklist[splitter] = false;
+            let splitter_states = blocks[splitter].clone();
+            for sources in &inverse {
+                // states that move into the splitter on this class, grouped by block
+                let mut hit: HashMap<usize, Vec<usize>> = HashMap::new();
+                for &to in &splitter_states {
+                    for &from in &sources[to] {
+                        hit.entry(block_of[from]).or_default().push(from);
+                    }
+                }
+                for (block, inside) in hit {
+                    if inside.len() == blocks[block].len() {
+                        continue;
+                    }
+                    let new_block = blocks.len();
+                    for &state in &inside {
+                        block_of[state] = new_block;
+                    }
+                    blocks[block].retain(|&s| block_of[s] == block);
+                    blocks.push(inside);
+                    if in_worklist[block] || blocks[new_block].len() <= blocks[block].len() {
+                        worklist.push(new_block);
+                        in_worklist.push(true);
+                    } else {
+                        in_worklist.push(false);
+                        worklist.push(block);
+                        in_worklist[block] = true;
+                    }
+                }
+            }
+        }
+
+        // renumber so the dead state's block is 0 and the rest follow in order
+        let mut renumber = vec![usize::MAX; blocks.len()];
+        renumber[block_of[DEAD]] = DEAD;
+        let mut count = 1;
+        for &block in &block_of {
+            if renumber[block] == usize::MAX {
+                renumber[block] = count;
+                count += 1;
+            }
+        }
+        let mut table = vec![Vec::new(); count];
+        let mut accept = vec![None; count];
+        for state in 0..states {
+            let id = renumber[block_of[state]];
+            if table[id].is_empty() {
+                table[id] = self.table[state].iter().map(|&to| renumber[block_of[to]]).collect();
+                accept[id] = self.accept[state];
+            }
+        }
+        Dfa { class_starts: self.class_starts.clone(), table, accept, start: renumber[block_of[self.start]] }
+    }
+}
+
+// ---------------------------------------------------------------------------
+// the generated lexer
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct GeneratedLexer {
+    pub rules: Vec<TokenRule>,
+    pub dfa: Dfa,
+    file: FileId,
+}
+
+impl GeneratedLexer {
+    pub fn from_spec(spec: &str) -> Result<GeneratedLexer, String> {
+        let rules = parse_spec(spec)?;
+        let mut nfa = Nfa::default();
+        let start = nfa.add_state();
+        for (index, rule) in rules.iter().enumerate() {
+            let regex = RegexParser::parse(&rule.pattern).map_err(|e| format!("{}: {}", rule.name, e))?;
+            let (s, e) = nfa.build(&regex);
+            nfa.states[start].epsilon.push(s);
+            nfa.states[e].accept = Some(index);
+        }
+        let dfa = Dfa::from_nfa(&nfa, start, &rules).minimize();
+        Ok(GeneratedLexer { rules, dfa, file: FileId::default() })
+    }
+
+    // the file that spans and errors point into, like Lexer::set_file
+    pub fn set_file(&mut self, file: FileId) {
+        self.file = file;
+    }
+
+    fn error(&self, error: LexError) -> LexError {
+        LexError { file: self.file, ..error }
+    }
+
+    // The DFA only says that no token starts at `pos`. Literals are read again
+    // for the exact error, otherwise the spec tells whether `pos` starts a token
+    // that can only go on one way, like `&` before `&&`
+    fn no_match_error(&self, input: &str, pos: usize) -> LexError {
+        let c = input[pos..].chars().next().unwrap();
+        if matches!(c, '"' | '\'')
+            && let Err(error) = read_literal(input, pos)
+        {
+            return error;
+        }
+        let (mut state, mut end) = (self.dfa.start, pos);
+        for next in input[pos..].chars() {
+            let after = self.dfa.step(state, next);
+            if after == DEAD {
+                break;
+            }
+            (state, end) = (after, end + next.len_utf8());
+        }
+        match self.dfa.only_next(state) {
+            Some(expected) if end > pos => LexError::expected_after(expected, &input[pos..end], end),
+            _ => LexError::unexpected_char(c, pos),
+        }
+    }
+
+    // the longest match starting at `pos`, as (end, rule)
+    fn longest_match(&self, input: &str, pos: usize) -> Option<(usize, usize)> {
+        let mut state = self.dfa.start;
+        let mut best = None;
+        for (offset, c) in input[pos..].char_indices() {
+            state = self.dfa.step(state, c);
+            if state == DEAD {
+                break;
+            }
+            if let Some(rule) = self.dfa.accept[state] {
+                best = Some((pos + offset + c.len_utf8(), rule));
+            }
+        }
+        best
+    }
+
+    pub fn tokenize(&self, input: &str, interner: &mut Interner) -> Result<Vec<SpannedToken>, LexError> {
+        let mut tokens = Vec::new();
+        let mut pos = 0;
+        while pos < input.len() {
+            let Some((end, rule)) = self.longest_match(input, pos) else {
+                return Err(self.error(self.no_match_error(input, pos)));
+            };
+            let name = &self.rules[rule].name;
+            if name != "skip" {
+                let token = match self.rules[rule].token {
+                    Some(token) => token,
+                    None => make_token(name, input, pos..end, interner).map_err(|error| self.error(error))?,
+                };
+                tokens.push(SpannedToken { token, span: Span { file: self.file, start: pos, end } });
+            }
+            pos = end;
+        }
+        tokens.push(SpannedToken { token: Token::EOI, span: Span { file: self.file, start: pos, end: pos } });
+        Ok(tokens)
+    }
+}
+
+// tokens with a payload, taken from the matched text. The pattern only
+// checked the shape of a literal's escapes, their values can still be wrong
+fn make_token(name: &str, input: &str, span: Range<usize>, interner: &mut Interner) -> Result<Token, LexError> {
+    let lexeme = &input[span.clone()];
+    let token = match name {
+        "ID" => Token::ID(interner.intern(lexeme)),
+        "LIT_INT32" => Token::LIT_INT32(lexeme.parse::<i32>().unwrap_or(0)),
+        "LIT_FLT32" => Token::LIT_FLT32(lexeme.parse::<f32>().unwrap_or(0.0)),
+        "LIT_BOOL" => Token::LIT_BOOL(lexeme == "true"),
+        "LIT_CHAR" => Token::LIT_CHAR(read_literal(input, span.start)?.chars().next().unwrap_or('\0')),
+        "LIT_STRING" => Token::LIT_STRING(interner.intern(&read_literal(input, span.start)?)),
+        _ => unreachable!("parse_spec only lets through payload tokens and ones in fixed_tokens"),
+    };
+    Ok(token)
+}
+
+// the text of the char or string literal at `start`, read the way Lexer reads
+// it (escapes through read_escape), so a bad literal gets the same error
+fn read_literal(input: &str, start: usize) -> Result<String, LexError> {
+    let peek = |pos: usize| input[pos..].chars().next();
+    let quote = peek(start).unwrap();
+    let mut pos = start + 1;
+    let mut text = String::new();
+    loop {
+        let next = peek(pos);
+        if quote == '\'' {
+            match (text.is_empty(), next) {
+                (true, None | Some('\'')) => return Err(LexError::invalid_char_literal(start)),
+                (false, Some('\'')) => return Ok(text),
+                (false, _) => return Err(LexError::char_literal_too_long(start)),
+                (true, Some(_)) => {}
+            }
+        }
+        let c = match next {
+            None => return Err(LexError::unterminated_string(start)),
+            Some('"') if quote == '"' => return Ok(text),
+            Some(c) => c,
+        };
+        pos += c.len_utf8();
+        if c == '\\' {
+            let (value, end) = read_escape(input, pos)?;
+            text.push(value);
+            pos = end;
+        } else {
+            text.push(c);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Lexer;
+    use crate::fuzz::Rng;
+    use crate::unlex::{round_trips, unlex};
+
+    fn generated() -> GeneratedLexer {
+        GeneratedLexer::from_spec(DEFAULT_SPEC).unwrap()
+    }
+
+    fn matches<'a>(lexer: &GeneratedLexer, pattern_input: &'a str) -> Option<&'a str> {
+        lexer.longest_match(pattern_input, 0).map(|(end, _)| &pattern_input[..end])
+    }
+
+    #[test]
+    fn regex_features() {
+        let spec = "ID 1 a(b|c)*d?\nLIT_INT32 1 x{2,3}\nSEMICOLON 1 [^a-z\\s]+";
+        let lexer = GeneratedLexer::from_spec(spec).unwrap();
+        assert_eq!(matches(&lexer, "abcbd!"), Some("abcbd"));
+        assert_eq!(matches(&lexer, "a"), Some("a"));
+        assert_eq!(matches(&lexer, "xxxx"), Some("xxx"));
+        assert_eq!(matches(&lexer, "x"), None);
+        assert_eq!(matches(&lexer, "+-é z"), Some("+-é"));
+        assert!(GeneratedLexer::from_spec("ID 1 (ab").is_err());
+        assert!(GeneratedLexer::from_spec("ID 1 *a").is_err());
+        assert!(GeneratedLexer::from_spec("NOPE 1 a").is_err());
+    }
+
+    #[test]
+    fn minimizing_merges_equivalent_states() {
+        // (a|b)*c and [ab]*c describe the same language
+        let one = GeneratedLexer::from_spec("ID 1 (a|b)*c").unwrap();
+        let two = GeneratedLexer::from_spec("ID 1 [ab]*c").unwrap();
+        assert_eq!(one.dfa.state_count(), two.dfa.state_count());
+        // dead, start/loop and accept
+        assert_eq!(one.dfa.state_count(), 3);
+    }
+
+    fn assert_same(lexer: &GeneratedLexer, input: &str) {
+        let mut interner = Interner::new();
+        let hand = Lexer::tokenize(input, &mut interner);
+        let generated = lexer.tokenize(input, &mut interner);
+        assert_eq!(hand, generated, "input {:?}", input);
+    }
+
+    #[test]
+    fn matches_hand_written_lexer_on_sample_program() {
+        let lexer = generated();
+        assert_same(&lexer, include_str!("input.txt"));
+        assert!(lexer.tokenize(include_str!("input.txt"), &mut Interner::new()).is_ok());
+    }
+
+    #[test]
+    fn spans_and_errors_point_into_the_lexers_file() {
+        let mut lexer = generated();
+        lexer.set_file(FileId(2));
+        let tokens = lexer.tokenize("let x = 1;", &mut Interner::new()).unwrap();
+        assert!(tokens.iter().all(|t| t.span.file == FileId(2)));
+        let error = lexer.tokenize("x = 'ab';", &mut Interner::new()).unwrap_err();
+        assert_eq!((error.file, error.pos), (FileId(2), 4));
+        assert_eq!(error.message, "Character literal must contain exactly one character");
+    }
+
+    #[test]
+    fn matches_hand_written_lexer_on_random_token_streams() {
+        let lexer = generated();
+        let mut rng = Rng(0xD1B54A32D192ED03);
+        let mut interner = Interner::new();
+        for _ in 0..2000 {
+            let tokens: Vec<Token> = (0..rng.below(15))
+                .map(|_| crate::unlex::tests::random_token(&mut rng, &mut interner))
+                .filter(round_trips)
+                .collect();
+            let text = unlex(&tokens, &interner).unwrap();
+            assert_same(&lexer, &text);
+            assert!(lexer.tokenize(&text, &mut interner).is_ok(), "{:?}", text);
+        }
+    }
+
+    #[test]
+    fn matches_hand_written_lexer_on_random_text() {
+        let lexer = generated();
+        let pieces = [
+            "a", "_", "Z9", "func", "int32", "true", "0", "42", "99999999999", ".", "1.", "1.5", " ", "\n",
+            "\u{a0}", "\"", "'", "\\", "\\x4", "\\x41", "\\xA0", "\\u{", "\\u{1F600}", "\\u{D800}", "}", "{", "(",
+            ")", "[", "]", ",", ":", ";", "-", ">", "<", "=", "!", "&", "|", "+", "*", "/", "%", "^", "é",
+            "#", "@",
+        ];
+        let mut rng = Rng(0x94D049BB133111EB);
+        for _ in 0..20000 {
+            let text: String = (0..rng.below(8)).map(|_| pieces[rng.below(pieces.len())]).collect();
+            assert_same(&lexer, &text);
+        }
+    }
+}
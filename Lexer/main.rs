@@ -3,8 +3,36 @@
 use std::env;
 use std::fs;
 //use std::io;
+use std::path::Path;
 use std::process;
-#[derive(Debug, Clone, PartialEq)]
+
+pub mod ast;
+pub mod bench;
+pub mod cfg;
+pub mod config;
+pub mod dot;
+pub mod dump;
+pub mod fuzz;
+#[cfg(test)]
+mod golden;
+pub mod incremental;
+pub mod intern;
+pub mod ir;
+pub mod json;
+pub mod lexgen;
+pub mod parser;
+pub mod source;
+pub mod unlex;
+
+use config::{LexerConfig, OperatorFamily};
+use intern::{Interner, Symbol};
+use source::{FileId, SourceMap};
+
+// transitions in a row that may pass without consuming input before the
+// lexer counts as stuck, more than the longest chain of LexerStates
+const MAX_IDLE_STEPS: usize = 16;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
 // suppress cammelCase warnings
 #[allow(non_camel_case_types)]
 pub enum Token {
@@ -13,24 +41,222 @@ pub enum Token {
     //seperators
     POINT, COMMA, COLON, SEMICOLON, ARROW_R,
     //arithmetic operators
-    ADD, SUB, MUL, DIV,
+    ADD, SUB, MUL, DIV, MOD,
     //relational ops
-    EQ, LT, GT, NEQ, NLT, NGT,
-    //no >= or <= ... NGT and NLT are equivalent
+    EQ, LT, GT, NEQ, NLT, NGT, LE, GE,
+    //LE and NGT are equivalent, as are GE and NLT
     //Logical operators
-    NOT, AND, OR,
-    //no XOR NAND NOR... (They can be expresed with these ops anyway)
-    ASSIGN,
+    NOT, AND, OR, XOR,
+    //no NAND NOR... (They can be expresed with these ops anyway)
+    ASSIGN, ADD_ASSIGN, SUB_ASSIGN, MUL_ASSIGN, DIV_ASSIGN,
     //keys
-    FUNC, LET, IF, ELSE, WHILE, PRINT, RETURN,
-    ID(String),
+    FUNC, LET, IF, ELSE, WHILE, PRINT, RETURN, STRUCT, IMPORT,
+    ID(Symbol),
     //Types
-    TYPE_INT32, TYPE_FLT32, TYPE_CHAR,
-    //no voids or bools...
-    LIT_INT32(i32), LIT_FLT32(f32), LIT_CHAR(char), LIT_STRING(String), //will having a lit string by no string type be an issue
+    TYPE_INT32, TYPE_FLT32, TYPE_CHAR, TYPE_STRING, TYPE_BOOL,
+    TYPE_VOID, //a func without ARROW_R returns void
+    LIT_INT32(i32), LIT_FLT32(f32), LIT_CHAR(char), LIT_STRING(Symbol), LIT_BOOL(bool),
+    EOI
+}
+
+// a Token without its payload, for comparing and collecting kinds of tokens
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[allow(non_camel_case_types)]
+pub enum TokenKind {
+    PARENS_L, PARENS_R, BRACKETS_L, BRACKETS_R, BRACES_L, BRACES_R,
+    POINT, COMMA, COLON, SEMICOLON, ARROW_R,
+    ADD, SUB, MUL, DIV, MOD,
+    EQ, LT, GT, NEQ, NLT, NGT, LE, GE,
+    NOT, AND, OR, XOR,
+    ASSIGN, ADD_ASSIGN, SUB_ASSIGN, MUL_ASSIGN, DIV_ASSIGN,
+    FUNC, LET, IF, ELSE, WHILE, PRINT, RETURN, STRUCT, IMPORT,
+    ID,
+    TYPE_INT32, TYPE_FLT32, TYPE_CHAR, TYPE_STRING, TYPE_BOOL, TYPE_VOID,
+    LIT_INT32, LIT_FLT32, LIT_CHAR, LIT_STRING, LIT_BOOL,
     EOI
 }
 
+impl Token {
+    pub fn kind(&self) -> TokenKind {
+        match self {
+            Token::PARENS_L => TokenKind::PARENS_L,
+            Token::PARENS_R => TokenKind::PARENS_R,
+            Token::BRACKETS_L => TokenKind::BRACKETS_L,
+            Token::BRACKETS_R => TokenKind::BRACKETS_R,
+            Token::BRACES_L => TokenKind::BRACES_L,
+            Token::BRACES_R => TokenKind::BRACES_R,
+            Token::POINT => TokenKind::POINT,
+            Token::COMMA => TokenKind::COMMA,
+            Token::COLON => TokenKind::COLON,
+            Token::SEMICOLON => TokenKind::SEMICOLON,
+            Token::ARROW_R => TokenKind::ARROW_R,
+            Token::ADD => TokenKind::ADD,
+            Token::SUB => TokenKind::SUB,
+            Token::MUL => TokenKind::MUL,
+            Token::DIV => TokenKind::DIV,
+            Token::MOD => TokenKind::MOD,
+            Token::EQ => TokenKind::EQ,
+            Token::LT => TokenKind::LT,
+            Token::GT => TokenKind::GT,
+            Token::NEQ => TokenKind::NEQ,
+            Token::NLT => TokenKind::NLT,
+            Token::NGT => TokenKind::NGT,
+            Token::LE => TokenKind::LE,
+            Token::GE => TokenKind::GE,
+            Token::NOT => TokenKind::NOT,
+            Token::AND => TokenKind::AND,
+            Token::OR => TokenKind::OR,
+            Token::XOR => TokenKind::XOR,
+            Token::ASSIGN => TokenKind::ASSIGN,
+            Token::ADD_ASSIGN => TokenKind::ADD_ASSIGN,
+            Token::SUB_ASSIGN => TokenKind::SUB_ASSIGN,
+            Token::MUL_ASSIGN => TokenKind::MUL_ASSIGN,
+            Token::DIV_ASSIGN => TokenKind::DIV_ASSIGN,
+            Token::FUNC => TokenKind::FUNC,
+            Token::LET => TokenKind::LET,
+            Token::IF => TokenKind::IF,
+            Token::ELSE => TokenKind::ELSE,
+            Token::WHILE => TokenKind::WHILE,
+            Token::PRINT => TokenKind::PRINT,
+            Token::RETURN => TokenKind::RETURN,
+            Token::STRUCT => TokenKind::STRUCT,
+            Token::IMPORT => TokenKind::IMPORT,
+            Token::ID(_) => TokenKind::ID,
+            Token::TYPE_INT32 => TokenKind::TYPE_INT32,
+            Token::TYPE_FLT32 => TokenKind::TYPE_FLT32,
+            Token::TYPE_CHAR => TokenKind::TYPE_CHAR,
+            Token::TYPE_STRING => TokenKind::TYPE_STRING,
+            Token::TYPE_BOOL => TokenKind::TYPE_BOOL,
+            Token::TYPE_VOID => TokenKind::TYPE_VOID,
+            Token::LIT_INT32(_) => TokenKind::LIT_INT32,
+            Token::LIT_FLT32(_) => TokenKind::LIT_FLT32,
+            Token::LIT_CHAR(_) => TokenKind::LIT_CHAR,
+            Token::LIT_STRING(_) => TokenKind::LIT_STRING,
+            Token::LIT_BOOL(_) => TokenKind::LIT_BOOL,
+            Token::EOI => TokenKind::EOI,
+        }
+    }
+}
+
+impl TokenKind {
+    pub const ALL: [TokenKind; 55] = [
+        TokenKind::PARENS_L, TokenKind::PARENS_R, TokenKind::BRACKETS_L, TokenKind::BRACKETS_R,
+        TokenKind::BRACES_L, TokenKind::BRACES_R, TokenKind::POINT, TokenKind::COMMA, TokenKind::COLON,
+        TokenKind::SEMICOLON, TokenKind::ARROW_R, TokenKind::ADD, TokenKind::SUB, TokenKind::MUL,
+        TokenKind::DIV, TokenKind::MOD, TokenKind::EQ, TokenKind::LT, TokenKind::GT, TokenKind::NEQ,
+        TokenKind::NLT, TokenKind::NGT, TokenKind::LE, TokenKind::GE, TokenKind::NOT, TokenKind::AND,
+        TokenKind::OR, TokenKind::XOR, TokenKind::ASSIGN, TokenKind::ADD_ASSIGN, TokenKind::SUB_ASSIGN,
+        TokenKind::MUL_ASSIGN, TokenKind::DIV_ASSIGN, TokenKind::FUNC, TokenKind::LET, TokenKind::IF,
+        TokenKind::ELSE, TokenKind::WHILE, TokenKind::PRINT, TokenKind::RETURN, TokenKind::STRUCT,
+        TokenKind::IMPORT, TokenKind::ID, TokenKind::TYPE_INT32, TokenKind::TYPE_FLT32, TokenKind::TYPE_CHAR,
+        TokenKind::TYPE_STRING, TokenKind::TYPE_BOOL, TokenKind::TYPE_VOID, TokenKind::LIT_INT32,
+        TokenKind::LIT_FLT32, TokenKind::LIT_CHAR, TokenKind::LIT_STRING, TokenKind::LIT_BOOL, TokenKind::EOI,
+    ];
+
+    // the token of this kind, for kinds without a payload
+    pub fn token(self) -> Option<Token> {
+        let token = match self {
+            TokenKind::PARENS_L => Token::PARENS_L,
+            TokenKind::PARENS_R => Token::PARENS_R,
+            TokenKind::BRACKETS_L => Token::BRACKETS_L,
+            TokenKind::BRACKETS_R => Token::BRACKETS_R,
+            TokenKind::BRACES_L => Token::BRACES_L,
+            TokenKind::BRACES_R => Token::BRACES_R,
+            TokenKind::POINT => Token::POINT,
+            TokenKind::COMMA => Token::COMMA,
+            TokenKind::COLON => Token::COLON,
+            TokenKind::SEMICOLON => Token::SEMICOLON,
+            TokenKind::ARROW_R => Token::ARROW_R,
+            TokenKind::ADD => Token::ADD,
+            TokenKind::SUB => Token::SUB,
+            TokenKind::MUL => Token::MUL,
+            TokenKind::DIV => Token::DIV,
+            TokenKind::MOD => Token::MOD,
+            TokenKind::EQ => Token::EQ,
+            TokenKind::LT => Token::LT,
+            TokenKind::GT => Token::GT,
+            TokenKind::NEQ => Token::NEQ,
+            TokenKind::NLT => Token::NLT,
+            TokenKind::NGT => Token::NGT,
+            TokenKind::LE => Token::LE,
+            TokenKind::GE => Token::GE,
+            TokenKind::NOT => Token::NOT,
+            TokenKind::AND => Token::AND,
+            TokenKind::OR => Token::OR,
+            TokenKind::XOR => Token::XOR,
+            TokenKind::ASSIGN => Token::ASSIGN,
+            TokenKind::ADD_ASSIGN => Token::ADD_ASSIGN,
+            TokenKind::SUB_ASSIGN => Token::SUB_ASSIGN,
+            TokenKind::MUL_ASSIGN => Token::MUL_ASSIGN,
+            TokenKind::DIV_ASSIGN => Token::DIV_ASSIGN,
+            TokenKind::FUNC => Token::FUNC,
+            TokenKind::LET => Token::LET,
+            TokenKind::IF => Token::IF,
+            TokenKind::ELSE => Token::ELSE,
+            TokenKind::WHILE => Token::WHILE,
+            TokenKind::PRINT => Token::PRINT,
+            TokenKind::RETURN => Token::RETURN,
+            TokenKind::STRUCT => Token::STRUCT,
+            TokenKind::IMPORT => Token::IMPORT,
+            TokenKind::TYPE_INT32 => Token::TYPE_INT32,
+            TokenKind::TYPE_FLT32 => Token::TYPE_FLT32,
+            TokenKind::TYPE_CHAR => Token::TYPE_CHAR,
+            TokenKind::TYPE_STRING => Token::TYPE_STRING,
+            TokenKind::TYPE_BOOL => Token::TYPE_BOOL,
+            TokenKind::TYPE_VOID => Token::TYPE_VOID,
+            TokenKind::EOI => Token::EOI,
+            _ => return None,
+        };
+        Some(token)
+    }
+}
+
+// a set of TokenKinds as a bit mask
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct TokenSet(u64);
+
+impl TokenSet {
+    pub const EMPTY: TokenSet = TokenSet(0);
+
+    pub const fn of(kinds: &[TokenKind]) -> TokenSet {
+        let mut bits = 0;
+        let mut i = 0;
+        while i < kinds.len() {
+            bits |= 1 << kinds[i] as u64;
+            i += 1;
+        }
+        TokenSet(bits)
+    }
+
+    pub const fn with(self, kind: TokenKind) -> TokenSet {
+        TokenSet(self.0 | 1 << kind as u64)
+    }
+
+    pub const fn union(self, other: TokenSet) -> TokenSet {
+        TokenSet(self.0 | other.0)
+    }
+
+    pub const fn minus(self, other: TokenSet) -> TokenSet {
+        TokenSet(self.0 & !other.0)
+    }
+
+    pub const fn contains(self, kind: TokenKind) -> bool {
+        self.0 & 1 << kind as u64 != 0
+    }
+
+    pub const fn is_superset(self, other: TokenSet) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    pub const fn is_empty(self) -> bool {
+        self.0 == 0
+    }
+
+    pub fn iter(self) -> impl Iterator<Item = TokenKind> {
+        TokenKind::ALL.into_iter().filter(move |&kind| self.contains(kind))
+    }
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub enum LexerState {
     // Initial state
@@ -53,10 +279,10 @@ pub enum LexerState {
     InReturn,
 
     // Multi-character operator states
-    InMinus,        // After '-' (could be SUB or ARROW_R if followed by '>')
+    InMinus,        // After '-' (could be SUB, SUB_ASSIGN or ARROW_R)
     InExclamation,  // After '!' (could be NOT, NEQ, NLT, or NGT)
-    InLessThan,     // After '<'
-    InGreaterThan,  // After '>' could be NGT, GT or Arrow_R
+    InLessThan,     // After '<' could be LT or LE
+    InGreaterThan,  // After '>' could be GT or GE
     // need to peek next char and see if it is a keyword or ID
     InCharType,
     InIntType,
@@ -69,6 +295,142 @@ pub enum LexerState {
     InPrint,
 
 }
+
+impl LexerState {
+    pub const ALL: [LexerState; 21] = [
+        LexerState::Start, LexerState::InEOI, LexerState::InIdentifier, LexerState::InIntLit,
+        LexerState::InFltLit, LexerState::InString, LexerState::InCharLit, LexerState::InFunc,
+        LexerState::InReturn, LexerState::InMinus, LexerState::InExclamation, LexerState::InLessThan,
+        LexerState::InGreaterThan, LexerState::InCharType, LexerState::InIntType, LexerState::InFltType,
+        LexerState::InLet, LexerState::InIf, LexerState::InElse, LexerState::InWhile, LexerState::InPrint,
+    ];
+}
+// byte offsets into one source file, end is exclusive
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Span {
+    pub file: FileId,
+    pub start: usize,
+    pub end: usize,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SpannedToken {
+    pub token: Token,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct LexError {
+    pub message: String,
+    pub file: FileId,
+    pub pos: usize,
+}
+
+impl LexError {
+    pub fn new(message: &str, pos: usize) -> Self {
+        LexError { message: message.to_string(), file: FileId::default(), pos }
+    }
+
+    // the errors both this lexer and the generated one (lexgen.rs) report are
+    // worded here, so the two can be compared error for error
+
+    pub fn unexpected_char(c: char, pos: usize) -> Self {
+        LexError::new(&format!("Unexpected character: '{}'", c), pos)
+    }
+
+    // `after` is the start of a token that can only go on with `expected`
+    pub fn expected_after(expected: char, after: &str, pos: usize) -> Self {
+        LexError::new(&format!("Expected '{}' after '{}'", expected, after), pos)
+    }
+
+    pub fn unterminated_string(start: usize) -> Self {
+        LexError::new("Unterminated string literal", start)
+    }
+
+    pub fn invalid_char_literal(start: usize) -> Self {
+        LexError::new("Invalid character literal", start)
+    }
+
+    pub fn char_literal_too_long(start: usize) -> Self {
+        LexError::new("Character literal must contain exactly one character", start)
+    }
+}
+
+// reads the escape after a '\\' at byte `pos` of `input`, returning the char it
+// stands for and the byte after it. Errors point at the char reading stopped
+// on. The generated lexer reads escapes with this too
+pub fn read_escape(input: &str, mut pos: usize) -> Result<(char, usize), LexError> {
+    let peek = |pos: usize| input[pos..].chars().next();
+    let Some(escaped) = peek(pos) else {
+        return Err(LexError::new("Unterminated escape sequence", pos));
+    };
+    pos += escaped.len_utf8();
+    let value = match escaped {
+        'n' => '\n',
+        't' => '\t',
+        'r' => '\r',
+        '0' => '\0',
+        '\\' => '\\',
+        '"' => '"',
+        '\'' => '\'',
+        // \x41 - exactly two hex digits, ASCII only
+        'x' => {
+            let mut digits = String::new();
+            for _ in 0..2 {
+                match peek(pos) {
+                    Some(c) if c.is_ascii_hexdigit() => {
+                        digits.push(c);
+                        pos += 1;
+                    }
+                    _ => return Err(LexError::new("Expected two hex digits after '\\x'", pos)),
+                }
+            }
+            let value = u8::from_str_radix(&digits, 16).unwrap();
+            if value > 0x7F {
+                return Err(LexError::new(&format!("'\\x{}' is not ASCII, write '\\u{{{}}}' instead", digits, digits), pos));
+            }
+            char::from(value)
+        }
+        // \u{1F600} - one to six hex digits in braces
+        'u' => {
+            if peek(pos) != Some('{') {
+                return Err(LexError::new("Expected '{' after '\\u'", pos));
+            }
+            pos += 1;
+            let mut digits = String::new();
+            loop {
+                match peek(pos) {
+                    Some('}') => {
+                        pos += 1;
+                        break;
+                    }
+                    Some(c) if c.is_ascii_hexdigit() && digits.len() < 6 => {
+                        digits.push(c);
+                        pos += 1;
+                    }
+                    _ => return Err(LexError::new("Invalid unicode escape", pos)),
+                }
+            }
+            if digits.is_empty() {
+                return Err(LexError::new("Empty unicode escape", pos));
+            }
+            let value = u32::from_str_radix(&digits, 16).unwrap();
+            match char::from_u32(value) {
+                Some(c) => c,
+                None => return Err(LexError::new(&format!("Invalid unicode scalar value {:X} in escape", value), pos)),
+            }
+        }
+        _ => escaped,
+    };
+    Ok((value, pos))
+}
+
+impl std::fmt::Display for LexError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{} at position {}", self.message, self.pos)
+    }
+}
+
 // suppress cammelCase warnings
 #[derive(Debug, Clone, PartialEq)]
 #[allow(non_camel_case_types)]
@@ -76,9 +438,20 @@ pub enum LexerState {
 pub struct Lexer {
     input:String,
     currState:LexerState,
-    inputPos:usize,
+    file:FileId,
+    inputPos:usize, //byte offset of the next char
+    tokenStart:usize,
     currToken:Token,
+    currSpan:Span,
     buffer:String,
+    config:LexerConfig,
+    interner:Interner, //what the ID and LIT_STRING symbols point into
+}
+
+impl Default for Lexer {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Lexer {
@@ -87,39 +460,148 @@ impl Lexer {
         Lexer {
             input: String::new(),
             currState: LexerState::Start,
+            file: FileId::default(),
             inputPos: 0,
+            tokenStart: 0,
             currToken: Token::EOI,
+            currSpan: Span { file: FileId::default(), start: 0, end: 0 },
             buffer: String::new(),
+            config: LexerConfig::default(),
+            interner: Interner::new(),
         }
     }
 
+    pub fn with_config(config: LexerConfig) -> Self {
+        Lexer { config, ..Lexer::new() }
+    }
+
+    pub fn interner(&self) -> &Interner {
+        &self.interner
+    }
+
+    // lets symbols from earlier runs keep their meaning in this one
+    pub fn set_interner(&mut self, interner: Interner) {
+        self.interner = interner;
+    }
+
+    pub fn take_interner(&mut self) -> Interner {
+        std::mem::take(&mut self.interner)
+    }
+
+    pub fn config(&self) -> &LexerConfig {
+        &self.config
+    }
+
+    // spans and errors from here on point into `file`
+    pub fn set_file(&mut self, file: FileId) {
+        self.file = file;
+    }
+
     fn set_input(&mut self, input: String) {
+        self.reset(input, 0);
+
+        //get first token
+        self.advance();
+    }
+
+    // start lexing `input` from byte offset `pos`, which must be a token boundary
+    pub fn reset(&mut self, input: String, pos: usize) {
         self.input = input;
-        self.inputPos = 0;
+        self.inputPos = pos;
+        self.tokenStart = pos;
         self.currState = LexerState::Start;
         self.currToken = Token::EOI;
-        (&mut self.buffer).clear();
+        self.currSpan = Span { file: self.file, start: pos, end: pos };
+        self.buffer.clear();
+    }
 
-        //get first token
-        self.advance();
+    // skips whitespace and gives the byte offset the next token starts at, so a
+    // caller resuming with reset can tell where lexing has got to
+    pub fn next_token_start(&mut self) -> usize {
+        self.skip_whitespace();
+        self.inputPos
+    }
+
+    // runs a single transition on `input` from `state`, as if `buffer` had
+    // already been collected, and returns its result, the state it left the
+    // lexer in and how many bytes it consumed. dot.rs draws the state machine
+    // from these
+    pub fn probe(state: LexerState, buffer: &str, input: &str) -> (Result<Option<Token>, LexError>, LexerState, usize) {
+        let mut lexer = Lexer::new();
+        lexer.reset(input.to_string(), 0);
+        lexer.currState = state;
+        lexer.buffer.push_str(buffer);
+        let result = lexer.transition();
+        (result, lexer.currState, lexer.inputPos)
+    }
+
+    // lexes the whole input, the last token is always EOI. Names and strings
+    // are interned into `interner`
+    pub fn tokenize(input: &str, interner: &mut Interner) -> Result<Vec<SpannedToken>, LexError> {
+        Lexer::tokenize_with_config(input, &LexerConfig::default(), interner)
+    }
+
+    pub fn tokenize_with_config(
+        input: &str,
+        config: &LexerConfig,
+        interner: &mut Interner,
+    ) -> Result<Vec<SpannedToken>, LexError> {
+        Lexer::tokenize_file(input, FileId::default(), config, interner)
+    }
+
+    // like tokenize_with_config, but spans and errors point into `file`
+    pub fn tokenize_file(
+        input: &str,
+        file: FileId,
+        config: &LexerConfig,
+        interner: &mut Interner,
+    ) -> Result<Vec<SpannedToken>, LexError> {
+        let mut lexer = Lexer::with_config(config.clone());
+        lexer.file = file;
+        lexer.interner = std::mem::take(interner);
+        lexer.reset(input.to_string(), 0);
+        let tokens = lexer.lex_rest();
+        *interner = lexer.interner;
+        tokens
+    }
+
+    fn lex_rest(&mut self) -> Result<Vec<SpannedToken>, LexError> {
+        let mut tokens = Vec::new();
+        loop {
+            let token = self.try_advance()?;
+            tokens.push(SpannedToken { token, span: self.curr_span() });
+            if token == Token::EOI {
+                return Ok(tokens);
+            }
+        }
     }
     pub fn get_next_token(&mut self) -> Token {
         self.advance()
     }
     fn peek_char(&self) -> Option<char> {
-        if self.inputPos < self.input.len() {
-            self.input.chars().nth(self.inputPos)
-        } else {
-            None
-        }
+        self.input[self.inputPos..].chars().next()
+    }
+
+    // one character past peek_char, used to tell a float's '.' from a field access
+    fn peek_next_char(&self) -> Option<char> {
+        self.input[self.inputPos..].chars().nth(1)
     }
 
     pub fn consume_char(&mut self) {
-        if self.inputPos < self.input.len() {
-            self.inputPos += 1;
+        if let Some(ch) = self.peek_char() {
+            self.inputPos += ch.len_utf8();
         }
     }
 
+    fn error(&self, message: &str) -> LexError {
+        LexError { file: self.file, ..LexError::new(message, self.inputPos) }
+    }
+
+    // operator chars whose family is switched off in the dialect are not part of the language
+    fn disabled(&self, c: char) -> LexError {
+        LexError::unexpected_char(c, self.tokenStart)
+    }
+
     fn skip_whitespace(&mut self) {
         while let Some(ch) = self.peek_char() {
             if ch.is_whitespace() {
@@ -131,53 +613,87 @@ impl Lexer {
     }
 
     pub fn advance(&mut self) -> Token {
+        match self.try_advance() {
+            Ok(token) => token,
+            Err(error) => panic!("{}", error),
+        }
+    }
+
+    // after try_advance returned `error`, moves past the bad character, or
+    // past a bad literal up to its closing quote or the end of the line, so
+    // the next try_advance can go on from there
+    pub fn skip_error(&mut self, error: &LexError) {
+        let input = std::mem::take(&mut self.input);
+        let start = self.tokenStart;
+        let resume = match input[start..].chars().next() {
+            Some(quote @ ('\'' | '"')) => match input[start + 1..].find([quote, '\n']) {
+                Some(i) if input[start + 1 + i..].starts_with(quote) => start + i + 2,
+                Some(i) => start + i + 1,
+                None => input.len(),
+            },
+            Some(c) => start + c.len_utf8(),
+            None => start,
+        };
+        self.reset(input, resume.max(error.pos));
+    }
+
+    pub fn try_advance(&mut self) -> Result<Token, LexError> {
         self.skip_whitespace();
+        self.tokenStart = self.inputPos;
 
         if self.inputPos >= self.input.len() {
             self.currToken = Token::EOI;
-            return Token::EOI;
+            self.currSpan = Span { file: self.file, start: self.inputPos, end: self.inputPos };
+            return Ok(Token::EOI);
         }
 
         self.currState = LexerState::Start;
         self.buffer.clear();
 
-        let mut attempts = 0;
-        while attempts < 1000 {
-            match self.transition() {
+        // a step that finishes no token has to consume a char or move to a
+        // state that will. Counting only the steps since the last char keeps
+        // long identifiers and strings from tripping the guard
+        let mut idle_steps = 0;
+        while idle_steps < MAX_IDLE_STEPS {
+            let pos = self.inputPos;
+            let step = self.transition().map_err(|error| LexError { file: self.file, ..error })?;
+            match step {
                 Some(token) => {
-                    self.currToken = token.clone();
-                    return token;
+                    self.currToken = token;
+                    self.currSpan = Span { file: self.file, start: self.tokenStart, end: self.inputPos };
+                    return Ok(token);
+                }
+                None if self.inputPos == pos => {
+                    idle_steps += 1;
                 }
                 None => {
-                    attempts += 1;
+                    idle_steps = 0;
                 }
             }
         }
 
-        panic!(
-            "Lexer stuck in transition loop at position {} with state {:?} and char {:?}",
-            self.inputPos,
+        Err(self.error(&format!(
+            "Lexer stuck in transition loop with state {:?} and char {:?}",
             self.currState,
             self.peek_char()
-        );
+        )))
     }
-    fn match_keyword_or_type(&self) -> Token {
-        match self.buffer.as_str() {
-            //put the strings of keywords or data types here and map them to tokens
-            "func" => Token::FUNC,
-            "let" => Token::LET,
-            "if" => Token::IF,
-            "else" => Token::ELSE,
-            "while" => Token::WHILE,
-            "print" => Token::PRINT,
-            "int32" => Token::TYPE_INT32,
-            "flt32" => Token::TYPE_FLT32,
-            "char" => Token::TYPE_CHAR,
-            _ => Token::ID(String::from(self.buffer.as_str())),
+    fn match_keyword_or_type(&mut self) -> Token {
+        // keywords and data types come from the dialect, see config::DEFAULT_KEYWORDS
+        match self.config.keyword(&self.buffer) {
+            Some(token) => *token,
+            None => Token::ID(self.interner.intern(&self.buffer)),
         }
     }
 
-    pub fn transition(&mut self) -> Option<Token> {
+    // called after a '\\' has been consumed inside a string or char literal
+    fn read_escape(&mut self) -> Result<char, LexError> {
+        let (value, end) = read_escape(&self.input, self.inputPos)?;
+        self.inputPos = end;
+        Ok(value)
+    }
+
+    pub fn transition(&mut self) -> Result<Option<Token>, LexError> {
         //nested match or if statements
         let ch = self.peek_char();
 
@@ -185,134 +701,167 @@ impl Lexer {
             LexerState::Start => {
                 match ch {
                     None => {
-                        return Some(Token::EOI);
+                        Ok(Some(Token::EOI))
                     }
 
                     Some('(') => {
                         self.consume_char();
-                        return Some(Token::PARENS_L);
+                        Ok(Some(Token::PARENS_L))
                     }
                     Some(')') =>{
                         self.consume_char();
-                        return Some(Token::PARENS_R);
+                        Ok(Some(Token::PARENS_R))
                     }
                     Some('[') => {
                         self.consume_char();
-                        return Some(Token::BRACKETS_L);
+                        Ok(Some(Token::BRACKETS_L))
                     }
                     Some(']') => {
                         self.consume_char();
-                        return Some(Token::BRACKETS_R);
+                        Ok(Some(Token::BRACKETS_R))
                     }
 
                     Some('{') => {
                         self.consume_char();
-                        return Some(Token::BRACES_L);
+                        Ok(Some(Token::BRACES_L))
                     }
                     Some('}') => {
                         self.consume_char();
-                        return Some(Token::BRACES_R);
+                        Ok(Some(Token::BRACES_R))
                     }
                     Some('.') => {
                         self.consume_char();
-                        return Some(Token::POINT);
+                        Ok(Some(Token::POINT))
                     }
                     Some(',') => {
                         self.consume_char();
-                        return Some(Token::COMMA);
+                        Ok(Some(Token::COMMA))
                     }
                     Some(':') => {
                         self.consume_char();
-                        return Some(Token::COLON);
+                        Ok(Some(Token::COLON))
                     }
                     Some(';') => {
                         self.consume_char();
-                        return Some(Token::SEMICOLON);
+                        Ok(Some(Token::SEMICOLON))
                     }
                     Some('=') => {
                         self.consume_char();
                         if self.peek_char() == Some('=') {
+                            // `==` is an error without relational operators, not two assignments
+                            if !self.config.allows(OperatorFamily::Relational) {
+                                return Err(self.disabled('='));
+                            }
                             self.consume_char();
-                            return Some(Token::EQ);
+                            return Ok(Some(Token::EQ));
                         }
-                        return Some(Token::ASSIGN);
+                        Ok(Some(Token::ASSIGN))
                     }
                     Some('+') => {
                         self.consume_char();
-                        return Some(Token::ADD);
+                        if self.peek_char() == Some('=') && self.config.allows(OperatorFamily::CompoundAssign) {
+                            self.consume_char();
+                            return Ok(Some(Token::ADD_ASSIGN));
+                        }
+                        if !self.config.allows(OperatorFamily::Arithmetic) {
+                            return Err(self.disabled('+'));
+                        }
+                        Ok(Some(Token::ADD))
                     }
                     Some('-') => {
                         self.consume_char();
                         self.currState = LexerState::InMinus;
-                        return None; // Continue processing
+                        Ok(None) // Continue processing
                     }
                     Some('*') => {
                         self.consume_char();
-                        return Some(Token::MUL);
+                        if self.peek_char() == Some('=') && self.config.allows(OperatorFamily::CompoundAssign) {
+                            self.consume_char();
+                            return Ok(Some(Token::MUL_ASSIGN));
+                        }
+                        if !self.config.allows(OperatorFamily::Arithmetic) {
+                            return Err(self.disabled('*'));
+                        }
+                        Ok(Some(Token::MUL))
                     }
                     Some('/') => {
                         self.consume_char();
-                        return Some(Token::DIV);
+                        if self.peek_char() == Some('=') && self.config.allows(OperatorFamily::CompoundAssign) {
+                            self.consume_char();
+                            return Ok(Some(Token::DIV_ASSIGN));
+                        }
+                        if !self.config.allows(OperatorFamily::Arithmetic) {
+                            return Err(self.disabled('/'));
+                        }
+                        Ok(Some(Token::DIV))
+                    }
+                    Some('%') if self.config.allows(OperatorFamily::Arithmetic) => {
+                        self.consume_char();
+                        Ok(Some(Token::MOD))
+                    }
+                    Some('^') if self.config.allows(OperatorFamily::Xor) => {
+                        self.consume_char();
+                        Ok(Some(Token::XOR))
                     }
                     Some('!') => {
                         self.consume_char();
                         self.currState = LexerState::InExclamation;
-                        return None; // Continue processing
+                        Ok(None) // Continue processing
                     }
-                    Some('<') => {
+                    Some('<') if self.config.allows(OperatorFamily::Relational) => {
                         self.consume_char();
                         self.currState = LexerState::InLessThan;
-                        return None; // Continue processing
+                        Ok(None) // Continue processing
                     }
-                    Some('>') => {
+                    Some('>') if self.config.allows(OperatorFamily::Relational) => {
                         self.consume_char();
                         self.currState = LexerState::InGreaterThan;
-                        return None; // Continue processing
+                        Ok(None) // Continue processing
                     }
-                    Some('&') => {
+                    Some('&') if self.config.allows(OperatorFamily::Logical) => {
                         self.consume_char();
                         if self.peek_char() == Some('&') {
                             self.consume_char();
-                            return Some(Token::AND);
+                            Ok(Some(Token::AND))
                         }
                         else {
-                            panic!("Expected '&' after '&'");
+                            Err(LexError::expected_after('&', "&", self.inputPos))
                         }
                     }
-                    Some('|') => {
+                    Some('|') if self.config.allows(OperatorFamily::Logical) => {
                         self.consume_char();
                         if self.peek_char() == Some('|') {
                             self.consume_char();
-                            return Some(Token::OR);
+                            Ok(Some(Token::OR))
                         }
                         else {
-                            panic!("Expected '|' after '|'");
+                            Err(LexError::expected_after('|', "|", self.inputPos))
                         }
                     }
                     Some('"') => {
                         self.consume_char(); // consume opening quote
                         self.currState = LexerState::InString;
-                        return None; // Continue processing
+                        Ok(None) // Continue processing
                     }
                     Some('\'') => {
                         self.consume_char(); // consume opening quote
                         self.currState = LexerState::InCharLit;
-                        return None; // Continue processing
+                        Ok(None) // Continue processing
                     }
                     Some(c) if c.is_ascii_digit() => {
                         self.buffer.push(c);
                         self.consume_char();
                         self.currState = LexerState::InIntLit;
-                        return None; // Continue processing
+                        Ok(None) // Continue processing
                     }
                     Some(c) if c.is_ascii_alphabetic() || c == '_' => {
                         self.buffer.push(c);
                         self.consume_char();
                         self.currState = LexerState::InIdentifier;
-                        return None; // Continue processing
+                        Ok(None) // Continue processing
                     }
                     Some(c) => {
-                        panic!("Unexpected character: '{}'", c);
+                        Err(LexError::unexpected_char(c, self.inputPos))
                     }
                 }
             }
@@ -323,12 +872,12 @@ impl Lexer {
                         // Continue building identifier
                         self.buffer.push(c);
                         self.consume_char();
-                        return None; // Keep processing
+                        Ok(None) // Keep processing
                     }
                     _ => {
                         // End of identifier - use helper to check if keyword/type
                         let token = self.match_keyword_or_type();
-                        return Some(token);
+                        Ok(Some(token))
                     }
                 }
             }
@@ -338,18 +887,19 @@ impl Lexer {
                     Some(c) if c.is_ascii_digit() => {
                         self.buffer.push(c);
                         self.consume_char();
-                        return None; // Keep processing
+                        Ok(None) // Keep processing
                     }
-                    Some('.') => {
+                    // only a float if a digit follows, so `1.x` is LIT_INT32 POINT ID
+                    Some('.') if self.peek_next_char().is_some_and(|c| c.is_ascii_digit()) => {
                         self.buffer.push('.');
                         self.consume_char();
                         self.currState = LexerState::InFltLit;
-                        return None; // Keep processing
+                        Ok(None) // Keep processing
                     }
                     _ => {
                         // End of integer
                         let value = self.buffer.parse::<i32>().unwrap_or(0);
-                        return Some(Token::LIT_INT32(value));
+                        Ok(Some(Token::LIT_INT32(value)))
                     }
                 }
             }
@@ -359,12 +909,12 @@ impl Lexer {
                     Some(c) if c.is_ascii_digit() => {
                         self.buffer.push(c);
                         self.consume_char();
-                        return None; // Keep processing
+                        Ok(None) // Keep processing
                     }
                     _ => {
                         // End of float
                         let value = self.buffer.parse::<f32>().unwrap_or(0.0);
-                        return Some(Token::LIT_FLT32(value));
+                        Ok(Some(Token::LIT_FLT32(value)))
                     }
                 }
             }
@@ -373,68 +923,42 @@ impl Lexer {
                 match ch {
                     Some('"') => {
                         self.consume_char();
-                        return Some(Token::LIT_STRING(self.buffer.clone()));
+                        Ok(Some(Token::LIT_STRING(self.interner.intern(&self.buffer))))
                     }
                     Some('\\') => {
                         self.consume_char();
-                        if let Some(escaped) = self.peek_char() {
-                            self.consume_char();
-                            let escaped_char = match escaped {
-                                'n' => '\n',
-                                't' => '\t',
-                                '\\' => '\\',
-                                '"' => '"',
-                                _ => escaped,
-                            };
-                            self.buffer.push(escaped_char);
-                        }
-                        return None;
+                        let escaped_char = self.read_escape()?;
+                        self.buffer.push(escaped_char);
+                        Ok(None)
                     }
                     Some(c) => {
                         self.buffer.push(c);
                         self.consume_char();
-                        return None;
+                        Ok(None)
                     }
                     None => {
-                        panic!(
-                            "Unterminated string literal starting at position {}",
-                            self.inputPos - self.buffer.len()
-                        );
+                        Err(LexError::unterminated_string(self.tokenStart))
                     }
                 }
             }
 
             LexerState::InCharLit => {
                 match ch {
-                    Some('\\') => {
-                        self.consume_char();
-                        if let Some(escaped) = self.peek_char() {
-                            self.consume_char();
-                            let escaped_char = match escaped {
-                                'n' => '\n',
-                                't' => '\t',
-                                '\\' => '\\',
-                                '\'' => '\'',
-                                _ => escaped,
-                            };
-                            self.buffer.push(escaped_char);
-                        }
-                        return None; // Keep processing
-                    }
                     Some(c) if c != '\'' => {
-                        self.buffer.push(c);
                         self.consume_char();
+                        let char_val = if c == '\\' { self.read_escape()? } else { c };
+                        self.buffer.push(char_val);
                         // Expect closing quote
                         if let Some('\'') = self.peek_char() {
                             self.consume_char();
                             let char_val = self.buffer.chars().next().unwrap_or('\0');
-                            return Some(Token::LIT_CHAR(char_val));
+                            Ok(Some(Token::LIT_CHAR(char_val)))
                         } else {
-                            panic!("Character literal must contain exactly one character");
+                            Err(LexError::char_literal_too_long(self.tokenStart))
                         }
                     }
                     _ => {
-                        panic!("Invalid character literal");
+                        Err(LexError::invalid_char_literal(self.tokenStart))
                     }
                 }
             }
@@ -443,32 +967,46 @@ impl Lexer {
                 match ch {
                     Some('>') => {
                         self.consume_char();
-                        return Some(Token::ARROW_R);
+                        Ok(Some(Token::ARROW_R))
+                    }
+                    Some('=') if self.config.allows(OperatorFamily::CompoundAssign) => {
+                        self.consume_char();
+                        Ok(Some(Token::SUB_ASSIGN))
+                    }
+                    _ if !self.config.allows(OperatorFamily::Arithmetic) => {
+                        Err(self.disabled('-'))
                     }
                     _ => {
                         // Don't consume next char, just return SUB
-                        return Some(Token::SUB);
+                        Ok(Some(Token::SUB))
                     }
                 }
             }
 
             LexerState::InExclamation => {
                 match ch {
-                    Some('=') => {
+                    Some('=') if self.config.allows(OperatorFamily::NegatedRelational) => {
                         self.consume_char();
-                        return Some(Token::NEQ);
+                        Ok(Some(Token::NEQ))
                     }
-                    Some('<') => {
+                    Some('<') if self.config.allows(OperatorFamily::NegatedRelational) => {
                         self.consume_char();
-                        return Some(Token::NLT);
+                        Ok(Some(Token::NLT))
                     }
-                    Some('>') => {
+                    Some('>') if self.config.allows(OperatorFamily::NegatedRelational) => {
                         self.consume_char();
-                        return Some(Token::NGT);
+                        Ok(Some(Token::NGT))
+                    }
+                    // like `==`, an error rather than `!` and a second operator
+                    Some('=' | '<' | '>') => {
+                        Err(self.disabled('!'))
+                    }
+                    _ if !self.config.allows(OperatorFamily::Logical) => {
+                        Err(self.disabled('!'))
                     }
                     _ => {
                         // Don't consume next char, just return NOT
-                        return Some(Token::NOT);
+                        Ok(Some(Token::NOT))
                     }
                 }
             }
@@ -477,18 +1015,26 @@ impl Lexer {
                 match ch {
                     Some('=') => {
                         self.consume_char();
-                        return Some(Token::EQ);
+                        Ok(Some(Token::LE))
                     }
                     _ => {
                         // Don't consume next char, just return LT
-                        return Some(Token::LT);
+                        Ok(Some(Token::LT))
                     }
                 }
             }
 
             LexerState::InGreaterThan => {
-                // GT is always just '>'
-                return Some(Token::GT);
+                match ch {
+                    Some('=') => {
+                        self.consume_char();
+                        Ok(Some(Token::GE))
+                    }
+                    _ => {
+                        // Don't consume next char, just return GT
+                        Ok(Some(Token::GT))
+                    }
+                }
             }
 
             // These states are not needed - handled by InIdentifier + match_keyword_or_type()
@@ -508,18 +1054,21 @@ impl Lexer {
             }
 
             LexerState::InEOI => {
-                return Some(Token::EOI);
+                Ok(Some(Token::EOI))
             }
         }
     }
     pub fn curr(&self) -> Token {
         //returns current token
-        self.currToken.clone()
+        self.currToken
+    }
+    pub fn curr_span(&self) -> Span {
+        self.currSpan
     }
     fn print_tokens(&mut self) {
         loop {
             let token = self.curr();
-            println!("{:?}", token);
+            println!("{}", self.interner.debug(&token));
 
             if token == Token::EOI {
                 break;
@@ -529,35 +1078,6 @@ impl Lexer {
         }
     }
 
-    pub fn expect(&mut self,expected:Token) {
-        if !self.token_matches(&self.curr(), &expected) { // if curr doesnt return a token
-            panic!("token not found");
-        }
-        self.advance();
-    }
-
-    pub fn accept(&mut self, expected: Token) -> bool {
-        if self.token_matches(&self.curr(), &expected) {
-            self.advance();
-            true
-        } else {
-            false
-        }
-    }
-
-    fn token_matches(&self, token1: &Token, token2: &Token) -> bool {
-        match (token1, token2) {
-            (Token::ID(_), Token::ID(_)) => true,
-            (Token::LIT_INT32(_), Token::LIT_INT32(_)) => true,
-            (Token::LIT_FLT32(_), Token::LIT_FLT32(_)) => true,
-            (Token::LIT_CHAR(_), Token::LIT_CHAR(_)) => true,
-            (Token::LIT_STRING(_), Token::LIT_STRING(_)) => true,
-            _ => token1 == token2,
-        }
-    }
-
-
-
 }
 fn main() {
     let args: Vec<String> = env::args().collect();
@@ -590,9 +1110,27 @@ fn main() {
         "listTokens" => {
             list_tokens();
         }
+        "lexerGraph" => {
+            handle_lexer_graph_command(&args[2..]);
+        }
         "tokenize" => {
             handle_tokenize_command(&args[2..]);
         }
+        "imports" => {
+            handle_imports_command(&args[2..]);
+        }
+        "check" => {
+            handle_check_command(&args[2..]);
+        }
+        "parse" => {
+            handle_parse_command(&args[2..]);
+        }
+        "fuzz" => {
+            handle_fuzz_command(&args[2..]);
+        }
+        "bench" => {
+            handle_bench_command(&args[2..]);
+        }
         _ => { //default case of switch statement
             println!("Unknown command: {}", command);
             println!("Try 'help' for a list of commands.");
@@ -602,22 +1140,23 @@ fn main() {
 }
 pub fn list_tokens() {
     use Token::*;
+    let mut interner = Interner::new();
     let tokens = vec![
         PARENS_L, PARENS_R, BRACKETS_L, BRACKETS_R, BRACES_L, BRACES_R,
         POINT, COMMA, COLON, SEMICOLON, ARROW_R,
-        ADD, SUB, MUL, DIV,
-        EQ, LT, GT, NEQ, NLT, NGT,
-        NOT, AND, OR,
-        ASSIGN,
-        FUNC, LET, IF, ELSE, WHILE, PRINT, RETURN,
-        ID("example_id".to_string()),
-        TYPE_INT32, TYPE_FLT32, TYPE_CHAR,
-        LIT_INT32(42), LIT_FLT32(3.14), LIT_CHAR('x'), LIT_STRING("hello".to_string()),
+        ADD, SUB, MUL, DIV, MOD,
+        EQ, LT, GT, NEQ, NLT, NGT, LE, GE,
+        NOT, AND, OR, XOR,
+        ASSIGN, ADD_ASSIGN, SUB_ASSIGN, MUL_ASSIGN, DIV_ASSIGN,
+        FUNC, LET, IF, ELSE, WHILE, PRINT, RETURN, STRUCT, IMPORT,
+        ID(interner.intern("example_id")),
+        TYPE_INT32, TYPE_FLT32, TYPE_CHAR, TYPE_STRING, TYPE_BOOL, TYPE_VOID,
+        LIT_INT32(42), LIT_FLT32(2.5), LIT_CHAR('x'), LIT_STRING(interner.intern("hello")), LIT_BOOL(true),
         EOI,
     ];
 
     for token in tokens {
-        println!("{:?}", token);
+        println!("{}", interner.debug(&token));
     }
 }
 
@@ -630,8 +1169,14 @@ fn print_general_help() {
     println!("    help [command]       shows help information for a command");
     println!("    print <file> [numbered]       Print arguments given");
     println!("    list        List all commands");
-    println!("    tokenize <file>       Lexically analyze a file");
-    println!("listTokens    List all tokens")
+    println!("    tokenize <file> [--dialect <file>]       Lexically analyze a file");
+    println!("    check <file> [--dialect <file>]       List every syntax error in a file and its imports");
+    println!("    parse <file> [--format json|sexp] [--emit dot-ast|dot-cfg|ir]    Print the syntax tree of a file");
+    println!("    imports <file> [--dialect <file>]       Load a file and everything it imports, in load order");
+    println!("    fuzz bytes|utf8 [--runs <n>] [--seed <n>]       Lex random inputs looking for lexer bugs");
+    println!("    bench [--functions <n>]       Time the lexer and parser on a large generated program");
+    println!("listTokens    List all tokens");
+    println!("    lexerGraph [file.dot]       Draw the lexer state machine as Graphviz DOT")
 }
 
 fn print_command_help(command:&str) {
@@ -671,14 +1216,121 @@ fn print_command_help(command:&str) {
             println!("lists all commands");
         }
 
+        "lexerGraph" => {
+            println!("lexerGraph - Draw the lexer state machine");
+            println!();
+            println!("Usage:");
+            println!("cargo run -- lexerGraph [file.dot]");
+            println!();
+            println!("Description:");
+            println!("Writes the lexer's states and transitions as a Graphviz DOT graph and lists");
+            println!("states that can never be reached from Start.");
+            println!("Arguments: ");
+            println!("    [file.dot] - Where to write the graph, prints it when left out (OPTIONAL)");
+        }
+
         "tokenize" => {
             println!("tokenize - Lexically analyze a file");
             println!();
             println!("Usage:");
-            println!("cargo run -- tokenize <file>");
+            println!("cargo run -- tokenize <file> [--dialect <dialect.json>]");
             println!();
             println!("Description:");
             println!("Reads the specified file and prints each token using the custom lexer.");
+            println!("Arguments: ");
+            println!("    <file> - The path of the file to tokenize (REQUIRED)");
+            println!("    --dialect <dialect.json> - Keyword and operator changes for a language variant (OPTIONAL)");
+        }
+
+        "check" => {
+            println!("check - List every syntax error in a file");
+            println!();
+            println!("Usage:");
+            println!("cargo run -- check <file> [--dialect <dialect.json>]");
+            println!();
+            println!("Description:");
+            println!("Parses the file and every file it imports and prints each syntax error as");
+            println!("file:line:col: message. After an error the parser skips to the next");
+            println!("statement and keeps going.");
+            println!("Arguments: ");
+            println!("    <file> - The path of the file to check (REQUIRED)");
+            println!("    --dialect <dialect.json> - Keyword and operator changes for a language variant (OPTIONAL)");
+        }
+
+        "parse" => {
+            println!("parse - Print the syntax tree of a file");
+            println!();
+            println!("Usage:");
+            println!("cargo run -- parse <file> [--format json|sexp] [--emit dot-ast|dot-cfg|ir] [--out <file>]");
+            println!("                          [--from json] [--dialect <dialect.json>]");
+            println!();
+            println!("Description:");
+            println!("Parses the file and prints its syntax tree with the kind, span and values of");
+            println!("every node. Imported files come first in the tree, their functions named");
+            println!("<module>.<name> after the file they are in. Syntax errors are listed instead");
+            println!("when there are any.");
+            println!("Arguments: ");
+            println!("    <file> - The path of the file to parse (REQUIRED)");
+            println!("    --format json|sexp - Output format, sexp when left out (OPTIONAL)");
+            println!("    --from json - The file is an AST written by --format json, not source (OPTIONAL)");
+            println!("    --emit dot-ast|dot-cfg|ir - Draw the syntax tree or each function's basic blocks");
+            println!("                                as Graphviz DOT, or print each function in SSA form,");
+            println!("                                instead (OPTIONAL)");
+            println!("    --out <file> - Where to write the --emit output, printed when left out (OPTIONAL)");
+            println!("    --dialect <dialect.json> - Keyword and operator changes for a language variant (OPTIONAL)");
+        }
+
+        "imports" => {
+            println!("imports - Load a program made of several files");
+            println!();
+            println!("Usage:");
+            println!("cargo run -- imports <file> [--dialect <dialect.json>]");
+            println!();
+            println!("Description:");
+            println!("Lexes the file and every file it pulls in with `import \"path\";`, relative to");
+            println!("the importing file, and prints them in load order. Errors name the file,");
+            println!("line and column they come from, import cycles included. Two imported");
+            println!("files with the same name, such as lib/util.src and app/util.src, are an");
+            println!("error since their functions would both be named util.<name>.");
+            println!("Arguments: ");
+            println!("    <file> - The main file of the program (REQUIRED)");
+            println!("    --dialect <dialect.json> - Keyword and operator changes for a language variant (OPTIONAL)");
+        }
+
+        "fuzz" => {
+            println!("fuzz - Lex random inputs and check the lexer's invariants");
+            println!();
+            println!("Usage:");
+            println!("cargo run -- fuzz bytes|utf8 [--runs <n>] [--seed <n>] [--corpus <dir>]");
+            println!("cargo run -- fuzz bytes|utf8 --replay <file>");
+            println!();
+            println!("Description:");
+            println!("Mutates the seed inputs and lexes the results, stopping at the first panic or");
+            println!("broken invariant: tokens must come in order, inside the input and on char");
+            println!("boundaries, and every token but EOI must consume input. Inputs that lex new");
+            println!("pairs of token kinds or new errors join the corpus. A failing input is saved");
+            println!("to fuzz/artifacts so --replay can run it again.");
+            println!("Arguments: ");
+            println!("    bytes|utf8 - Mutate raw bytes, or only valid UTF-8 a char at a time (REQUIRED)");
+            println!("    --runs <n> - How many mutated inputs to try, 100000 when left out (OPTIONAL)");
+            println!("    --seed <n> - Seed for the random mutations (OPTIONAL)");
+            println!("    --corpus <dir> - Seed inputs, tests/lex/*.src and input.txt when left out (OPTIONAL)");
+            println!("    --replay <file> - Run the target on one input instead of fuzzing (OPTIONAL)");
+        }
+
+        "bench" => {
+            println!("bench - Time the lexer and parser on a large generated program");
+            println!();
+            println!("Usage:");
+            println!("cargo run --release --features count-allocations -- bench [--functions <n>] [--out <file>]");
+            println!();
+            println!("Description:");
+            println!("Generates a program of n functions that reuse a small set of names, then lexes");
+            println!("and parses it, printing the time taken. Built with the count-allocations");
+            println!("feature it also prints how many heap allocations were made.");
+            println!("Arguments: ");
+            println!("    --functions <n> - Size of the generated program, 20000 when left out (OPTIONAL)");
+            println!("    --out <file> - Also write the generated program to a file (OPTIONAL)");
         }
 
         _ => { //default case of switch statement
@@ -719,16 +1371,17 @@ fn handle_print_command(args: &[String]) {
 fn handle_tokenize_command(args: &[String]) {
     if args.is_empty() {
         println!("No file specified");
-        println!("USAGE: cargo run -- tokenize <file>");
+        println!("USAGE: cargo run -- tokenize <file> [--dialect <dialect.json>]");
         println!("Try 'help tokenize' for more information");
         process::exit(0);
     }
 
     let file_path = &args[0];
+    let config = dialect_arg(args);
 
     match fs::read_to_string(file_path) {
         Ok(contents) => {
-            let mut lexer = Lexer::new();
+            let mut lexer = Lexer::with_config(config);
             lexer.set_input(contents);
             lexer.print_tokens();
         }
@@ -738,6 +1391,242 @@ fn handle_tokenize_command(args: &[String]) {
         }
     }
 }
+// the config named by `--dialect <file>`, or the default language
+fn dialect_arg(args: &[String]) -> LexerConfig {
+    let Some(dialect_path) = flag_value(args, "--dialect") else {
+        return LexerConfig::default();
+    };
+    match LexerConfig::load(dialect_path) {
+        Ok(config) => config,
+        Err(error) => {
+            println!("{}", error);
+            process::exit(1);
+        }
+    }
+}
+fn handle_check_command(args: &[String]) {
+    if args.is_empty() {
+        println!("No file specified");
+        println!("USAGE: cargo run -- check <file> [--dialect <dialect.json>]");
+        println!("Try 'help check' for more information");
+        process::exit(0);
+    }
+
+    let file_path = &args[0];
+    let config = dialect_arg(args);
+    let mut program = load_program_or_exit(file_path, &config);
+    let (_, errors) = source::parse_program(&mut program, &config);
+    print!("{}", check_report(file_path, &program.sources, &errors));
+    if !errors.is_empty() {
+        process::exit(1);
+    }
+}
+// what `check` prints, every syntax error as file:line:col: message and their
+// count, or that `name` has none
+fn check_report(name: &str, sources: &SourceMap, errors: &[parser::ParseError]) -> String {
+    if errors.is_empty() {
+        return format!("{}: no syntax errors\n", name);
+    }
+    let mut report = String::new();
+    for error in errors {
+        report.push_str(&format!("{}\n", sources.describe(error.span, &error.message)));
+    }
+    report.push_str(&format!("{} syntax error(s)\n", errors.len()));
+    report
+}
+fn load_program_or_exit(file_path: &str, config: &LexerConfig) -> source::Program {
+    match source::load_program(file_path, config) {
+        Ok(program) => program,
+        Err(error) => {
+            println!("{}", error);
+            process::exit(1);
+        }
+    }
+}
+// the value after `--name`, if the flag is given
+fn flag_value<'a>(args: &'a [String], name: &str) -> Option<&'a str> {
+    let index = args.iter().position(|arg| arg == name)?;
+    match args.get(index + 1) {
+        Some(value) => Some(value),
+        None => {
+            println!("No value given after {}", name);
+            process::exit(1);
+        }
+    }
+}
+fn handle_parse_command(args: &[String]) {
+    if args.is_empty() {
+        println!("No file specified");
+        println!("USAGE: cargo run -- parse <file> [--format json|sexp] [--from json]");
+        println!("Try 'help parse' for more information");
+        process::exit(0);
+    }
+
+    let file_path = &args[0];
+    let mut interner = Interner::new();
+    let program = match flag_value(args, "--from") {
+        None => {
+            let config = dialect_arg(args);
+            let mut loaded = load_program_or_exit(file_path, &config);
+            let (program, errors) = source::parse_program(&mut loaded, &config);
+            if !errors.is_empty() {
+                print!("{}", check_report(file_path, &loaded.sources, &errors));
+                process::exit(1);
+            }
+            interner = loaded.interner;
+            program
+        }
+        Some("json") => {
+            let contents = match fs::read_to_string(file_path) {
+                Ok(contents) => contents,
+                Err(error) => {
+                    println!("Error reading file {}: {}", file_path, error);
+                    process::exit(1);
+                }
+            };
+            match dump::program_from_json(&contents, &mut interner) {
+                Ok(program) => program,
+                Err(error) => {
+                    println!("Error in AST {}: {}", file_path, error);
+                    process::exit(1);
+                }
+            }
+        }
+        Some(other) => {
+            println!("Unknown input format: {}", other);
+            process::exit(1);
+        }
+    };
+
+    if let Some(emit) = flag_value(args, "--emit") {
+        let output = match emit {
+            "dot-ast" => dot::ast_graph(&program, &interner),
+            "dot-cfg" => dot::cfg_graph(&program, &interner),
+            "ir" => ir::program_text(&ir::lower_program(&program, &interner), &interner),
+            other => {
+                println!("Unknown --emit target: {}", other);
+                process::exit(1);
+            }
+        };
+        match flag_value(args, "--out") {
+            Some(out_path) => {
+                if let Err(error) = fs::write(out_path, &output) {
+                    println!("Error writing file {}: {}", out_path, error);
+                    process::exit(1);
+                }
+                println!("Wrote {}", out_path);
+            }
+            None => print!("{}", output),
+        }
+        return;
+    }
+
+    match flag_value(args, "--format") {
+        None | Some("sexp") => print!("{}", dump::program_to_sexp(&program, &interner)),
+        Some("json") => print!("{}", json::write(&dump::program_to_json(&program, &interner))),
+        Some(other) => {
+            println!("Unknown format: {}", other);
+            process::exit(1);
+        }
+    }
+}
+fn handle_fuzz_command(args: &[String]) {
+    let target = match args.first().map(String::as_str) {
+        Some("bytes") => fuzz::Target::Bytes,
+        Some("utf8") => fuzz::Target::Utf8,
+        _ => {
+            println!("No fuzz target specified");
+            println!("USAGE: cargo run -- fuzz bytes|utf8 [--runs <n>] [--seed <n>] [--corpus <dir>]");
+            println!("Try 'help fuzz' for more information");
+            process::exit(0);
+        }
+    };
+    let number = |name: &str, default: u64| match flag_value(args, name).map(str::parse::<u64>) {
+        None => default,
+        Some(Ok(value)) => value,
+        Some(Err(_)) => {
+            println!("{} must be a number", name);
+            process::exit(1);
+        }
+    };
+
+    if let Some(replay_path) = flag_value(args, "--replay") {
+        let input = fs::read(replay_path).unwrap_or_else(|error| {
+            println!("Error reading file {}: {}", replay_path, error);
+            process::exit(1);
+        });
+        target.run(&input);
+        println!("{} passed on {}", target.name(), replay_path);
+        return;
+    }
+
+    let seeds: Vec<Vec<u8>> = match flag_value(args, "--corpus") {
+        Some(corpus_dir) => match fs::read_dir(corpus_dir) {
+            Ok(entries) => entries.filter_map(|entry| fs::read(entry.ok()?.path()).ok()).collect(),
+            Err(error) => {
+                println!("Error reading corpus {}: {}", corpus_dir, error);
+                process::exit(1);
+            }
+        },
+        None => fuzz::default_seeds(Path::new(".")).unwrap_or_else(|error| {
+            println!("{}", error);
+            process::exit(1);
+        }),
+    };
+    let runs = number("--runs", 100_000) as usize;
+    let seed = number("--seed", 1);
+    let report = fuzz::fuzz(target, seeds, runs, seed);
+    println!("{}: {} runs, corpus of {} inputs", target.name(), report.runs, report.corpus);
+
+    if let Some(failure) = report.failure {
+        // named after the input, so the same failure is saved once
+        let hash = failure.input.iter().fold(0xcbf29ce484222325u64, |h, &b| (h ^ b as u64).wrapping_mul(0x100000001b3));
+        let artifact = format!("fuzz/artifacts/{}-{:016x}", target.name(), hash);
+        println!("Failed: {}", failure.message);
+        println!("Input: {:?}", String::from_utf8_lossy(&failure.input));
+        match fs::create_dir_all("fuzz/artifacts").and_then(|_| fs::write(&artifact, &failure.input)) {
+            Ok(()) => println!("Saved to {}, rerun it with --replay {}", artifact, artifact),
+            Err(error) => println!("Error writing {}: {}", artifact, error),
+        }
+        process::exit(1);
+    }
+}
+fn handle_bench_command(args: &[String]) {
+    let functions = match flag_value(args, "--functions").map(str::parse::<usize>) {
+        None => 20_000,
+        Some(Ok(functions)) => functions,
+        Some(Err(_)) => {
+            println!("--functions must be a number");
+            process::exit(1);
+        }
+    };
+    let program = bench::generate_program(functions, 1);
+    if let Some(out_path) = flag_value(args, "--out")
+        && let Err(error) = fs::write(out_path, &program)
+    {
+        println!("Error writing file {}: {}", out_path, error);
+        process::exit(1);
+    }
+
+    let report = bench::run(&program);
+    println!("{} functions, {} bytes, {} tokens", functions, program.len(), report.tokens);
+    for (name, measurement) in [("lex", &report.lex), ("parse", &report.parse)] {
+        match measurement.allocations {
+            Some((count, bytes)) => println!(
+                "{:<6} {:>9.2?} {:>10} allocations ({:.2} per token) {:>12} bytes",
+                name,
+                measurement.elapsed,
+                count,
+                count as f64 / report.tokens as f64,
+                bytes
+            ),
+            None => println!("{:<6} {:>9.2?}", name, measurement.elapsed),
+        }
+    }
+    if report.lex.allocations.is_none() {
+        println!("Build with --features count-allocations to count heap allocations too");
+    }
+}
 fn list_commands() {
     println!("Available commands:");
     println!("list - List all commands");
@@ -745,5 +1634,215 @@ fn list_commands() {
     println!("print - Print arguments given");
     println!("listTokens - List all tokens");
     println!("tokenize - Lexically analyze a file");
+    println!("lexerGraph - Draw the lexer state machine");
+    println!("imports - Load a file and the files it imports");
+    println!("check - List every syntax error in a file");
+    println!("parse - Print the syntax tree of a file");
+    println!("fuzz - Lex random inputs and check the lexer's invariants");
+    println!("bench - Time the lexer and parser on a large generated program");
+}
+fn handle_imports_command(args: &[String]) {
+    let Some(file_path) = args.first() else {
+        println!("No file specified");
+        println!("USAGE: cargo run -- imports <file> [--dialect <dialect.json>]");
+        println!("Try 'help imports' for more information");
+        process::exit(0);
+    };
+
+    let program = load_program_or_exit(file_path, &dialect_arg(args));
+    for file in &program.files {
+        let imports: Vec<&str> = file.imports.iter().map(|&id| program.sources.name(id)).collect();
+        print!("{} ({} tokens)", program.sources.name(file.id), file.tokens.len());
+        if !imports.is_empty() {
+            print!(" imports {}", imports.join(", "));
+        }
+        println!();
+    }
+    let errors: Vec<&LexError> = program.files.iter().flat_map(|file| &file.lex_errors).collect();
+    for error in &errors {
+        println!("{}", program.sources.describe_lex_error(error));
+    }
+    if !errors.is_empty() {
+        process::exit(1);
+    }
+}
+fn handle_lexer_graph_command(args: &[String]) {
+    let graph = dot::lexer_graph();
+    match args.first() {
+        Some(file_path) => {
+            if let Err(error) = fs::write(file_path, &graph.dot) {
+                println!("Error writing file {}: {}", file_path, error);
+                process::exit(1);
+            }
+            println!("Wrote {}", file_path);
+        }
+        None => print!("{}", graph.dot),
+    }
+    for state in &graph.unreachable {
+        eprintln!("warning: {:?} can never be reached from Start", state);
+    }
 }
 //paste in commandline tool here and add in the functions of the lexer to the tool
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lex_all(input: &str, interner: &mut Interner) -> Vec<Token> {
+        let mut lexer = Lexer::new();
+        lexer.set_interner(std::mem::take(interner));
+        lexer.set_input(input.to_string());
+        let mut tokens = Vec::new();
+        loop {
+            let token = lexer.curr();
+            if token == Token::EOI {
+                *interner = lexer.take_interner();
+                return tokens;
+            }
+            tokens.push(token);
+            lexer.advance();
+        }
+    }
+
+    #[test]
+    fn every_operator_spelling() {
+        let table = [
+            ("+", Token::ADD),
+            ("-", Token::SUB),
+            ("*", Token::MUL),
+            ("/", Token::DIV),
+            ("%", Token::MOD),
+            ("==", Token::EQ),
+            ("<", Token::LT),
+            (">", Token::GT),
+            ("!=", Token::NEQ),
+            ("!<", Token::NLT),
+            ("!>", Token::NGT),
+            ("<=", Token::LE),
+            (">=", Token::GE),
+            ("!", Token::NOT),
+            ("&&", Token::AND),
+            ("||", Token::OR),
+            ("^", Token::XOR),
+            ("=", Token::ASSIGN),
+            ("+=", Token::ADD_ASSIGN),
+            ("-=", Token::SUB_ASSIGN),
+            ("*=", Token::MUL_ASSIGN),
+            ("/=", Token::DIV_ASSIGN),
+            ("->", Token::ARROW_R),
+        ];
+        let mut interner = Interner::new();
+        let x = Token::ID(interner.intern("x"));
+        for (spelling, expected) in table {
+            assert_eq!(lex_all(spelling, &mut interner), vec![expected], "lexing {:?}", spelling);
+            // same result when something follows the operator
+            let followed = format!("{}x", spelling);
+            assert_eq!(lex_all(&followed, &mut interner), vec![expected, x], "lexing {:?}", followed);
+        }
+    }
+
+    #[test]
+    fn point_is_field_access_unless_digits_follow() {
+        let mut interner = Interner::new();
+        let mut id = |s: &str| Token::ID(interner.intern(s));
+        let table = [
+            ("p.x", vec![id("p"), Token::POINT, id("x")]),
+            ("a.b.c", vec![id("a"), Token::POINT, id("b"), Token::POINT, id("c")]),
+            ("1.5", vec![Token::LIT_FLT32(1.5)]),
+            ("1.x", vec![Token::LIT_INT32(1), Token::POINT, id("x")]),
+            ("1.", vec![Token::LIT_INT32(1), Token::POINT]),
+            ("2.5.y", vec![Token::LIT_FLT32(2.5), Token::POINT, id("y")]),
+        ];
+        for (input, expected) in table {
+            assert_eq!(lex_all(input, &mut interner), expected, "lexing {:?}", input);
+        }
+    }
+
+    #[test]
+    fn operators_use_maximal_munch() {
+        let mut interner = Interner::new();
+        let (a, b) = (Token::ID(interner.intern("a")), Token::ID(interner.intern("b")));
+        let table = [
+            ("<==", vec![Token::LE, Token::ASSIGN]),
+            (">==", vec![Token::GE, Token::ASSIGN]),
+            ("===", vec![Token::EQ, Token::ASSIGN]),
+            ("->=", vec![Token::ARROW_R, Token::ASSIGN]),
+            ("-=>", vec![Token::SUB_ASSIGN, Token::GT]),
+            ("!<=", vec![Token::NLT, Token::ASSIGN]),
+            ("!==", vec![Token::NEQ, Token::ASSIGN]),
+            ("+==", vec![Token::ADD_ASSIGN, Token::ASSIGN]),
+            ("< =", vec![Token::LT, Token::ASSIGN]),
+            ("> =", vec![Token::GT, Token::ASSIGN]),
+            ("^=", vec![Token::XOR, Token::ASSIGN]),
+            ("%=", vec![Token::MOD, Token::ASSIGN]),
+            ("a<=b", vec![a, Token::LE, b]),
+        ];
+        for (input, expected) in table {
+            assert_eq!(lex_all(input, &mut interner), expected, "lexing {:?}", input);
+        }
+    }
+
+    #[test]
+    fn skip_error_resumes_after_the_bad_text() {
+        let mut lexer = Lexer::new();
+        lexer.reset("a $ 'xy' \"open\nb".to_string(), 0);
+        let mut kinds = Vec::new();
+        let mut errors = Vec::new();
+        loop {
+            match lexer.try_advance() {
+                Ok(Token::EOI) => break,
+                Ok(token) => kinds.push(token.kind()),
+                Err(error) => {
+                    errors.push(error.pos);
+                    lexer.skip_error(&error);
+                }
+            }
+        }
+        assert_eq!(kinds, vec![TokenKind::ID, TokenKind::ID]);
+        assert_eq!(errors.len(), 3);
+    }
+
+    #[test]
+    fn escape_sequences() {
+        let table = [
+            (r"'\n'", Ok('\n')),
+            (r"'\t'", Ok('\t')),
+            (r"'\r'", Ok('\r')),
+            (r"'\0'", Ok('\0')),
+            (r"'\\'", Ok('\\')),
+            (r"'\''", Ok('\'')),
+            (r#"'\"'"#, Ok('"')),
+            (r"'\x41'", Ok('A')),
+            (r"'\x7f'", Ok('\x7F')),
+            (r"'\u{263A}'", Ok('\u{263A}')),
+            (r"'\x80'", Err("'\\x80' is not ASCII, write '\\u{80}' instead at position 5")),
+            (r"'\xFF'", Err("'\\xFF' is not ASCII, write '\\u{FF}' instead at position 5")),
+            (r"'\x4'", Err("Expected two hex digits after '\\x' at position 4")),
+            (r"'\u{D800}'", Err("Invalid unicode scalar value D800 in escape at position 9")),
+        ];
+        for (input, expected) in table {
+            let lexed = Lexer::tokenize(input, &mut Interner::new()).map_err(|e| e.to_string());
+            let expected = expected.map(|c| vec![Token::LIT_CHAR(c), Token::EOI]).map_err(str::to_string);
+            assert_eq!(lexed.map(|tokens| tokens.into_iter().map(|t| t.token).collect()), expected, "lexing {}", input);
+        }
+    }
+
+    #[test]
+    fn token_kinds_and_sets() {
+        for (i, kind) in TokenKind::ALL.into_iter().enumerate() {
+            assert_eq!(kind as usize, i, "TokenKind::ALL is out of order at {:?}", kind);
+            if let Some(token) = kind.token() {
+                assert_eq!(token.kind(), kind);
+            }
+        }
+        assert_eq!(Token::ID(Interner::new().intern("x")).kind(), TokenKind::ID);
+        assert_eq!(Token::LIT_BOOL(false).kind(), TokenKind::LIT_BOOL);
+        assert_eq!(TokenKind::LIT_INT32.token(), None);
+
+        let set = TokenSet::of(&[TokenKind::EOI, TokenKind::PARENS_L]).with(TokenKind::ID);
+        assert_eq!(set.iter().collect::<Vec<_>>(), vec![TokenKind::PARENS_L, TokenKind::ID, TokenKind::EOI]);
+        assert!(set.is_superset(TokenSet::of(&[TokenKind::ID])));
+        assert!(set.minus(set).is_empty());
+        assert!(!set.contains(TokenKind::PARENS_R));
+    }
+}
@@ -0,0 +1,705 @@
+// Recursive descent parser for the toy language. A syntax error does not stop
+// the parse: it is recorded, the parser skips ahead to a synchronisation point
+// (past a ';', or up to a closing bracket or brace or a statement keyword) and
+// carries on, so one run reports every error in a file.
+use crate::ast::*;
+use crate::config::{DEFAULT_KEYWORDS, LexerConfig};
+use crate::intern::{Interner, Symbol};
+use crate::source::{self, FileId};
+use crate::{LexError, Span, SpannedToken, Token, TokenKind, TokenSet, unlex};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParseError {
+    pub message: String,
+    pub span: Span,
+}
+
+// passed up to the nearest synchronisation point once the error is recorded
+struct Recover;
+type Parsed<T> = Result<T, Recover>;
+
+// FIRST sets of the grammar
+const ITEM_FIRST: TokenSet = TokenSet::of(&[TokenKind::FUNC, TokenKind::STRUCT, TokenKind::IMPORT, TokenKind::LET]);
+const TYPE_FIRST: TokenSet = TokenSet::of(&[
+    TokenKind::TYPE_INT32,
+    TokenKind::TYPE_FLT32,
+    TokenKind::TYPE_CHAR,
+    TokenKind::TYPE_STRING,
+    TokenKind::TYPE_BOOL,
+    TokenKind::TYPE_VOID,
+    TokenKind::ID,
+]);
+pub const UNARY_OPERATORS: TokenSet = TokenSet::of(&[TokenKind::NOT, TokenKind::SUB]);
+const PRIMARY_FIRST: TokenSet = TokenSet::of(&[
+    TokenKind::LIT_INT32,
+    TokenKind::LIT_FLT32,
+    TokenKind::LIT_CHAR,
+    TokenKind::LIT_STRING,
+    TokenKind::LIT_BOOL,
+    TokenKind::ID,
+    TokenKind::PARENS_L,
+]);
+const EXPR_FIRST: TokenSet = UNARY_OPERATORS.union(PRIMARY_FIRST);
+const BLOCK_FIRST: TokenSet = TokenSet::of(&[TokenKind::BRACKETS_L, TokenKind::BRACES_L]);
+const STMT_FIRST: TokenSet = TokenSet::of(&[
+    TokenKind::LET,
+    TokenKind::IF,
+    TokenKind::WHILE,
+    TokenKind::RETURN,
+    TokenKind::PRINT,
+])
+.union(BLOCK_FIRST)
+.union(EXPR_FIRST);
+
+pub const BINARY_OPERATORS: TokenSet = TokenSet::of(&[
+    TokenKind::OR,
+    TokenKind::XOR,
+    TokenKind::AND,
+    TokenKind::EQ,
+    TokenKind::NEQ,
+    TokenKind::LT,
+    TokenKind::GT,
+    TokenKind::LE,
+    TokenKind::GE,
+    TokenKind::NLT,
+    TokenKind::NGT,
+    TokenKind::ADD,
+    TokenKind::SUB,
+    TokenKind::MUL,
+    TokenKind::DIV,
+    TokenKind::MOD,
+]);
+pub const ASSIGN_OPERATORS: TokenSet = TokenSet::of(&[
+    TokenKind::ASSIGN,
+    TokenKind::ADD_ASSIGN,
+    TokenKind::SUB_ASSIGN,
+    TokenKind::MUL_ASSIGN,
+    TokenKind::DIV_ASSIGN,
+]);
+
+// panic mode stops in front of these
+const STATEMENT_KEYWORDS: TokenSet = TokenSet::of(&[
+    TokenKind::LET,
+    TokenKind::IF,
+    TokenKind::WHILE,
+    TokenKind::RETURN,
+    TokenKind::PRINT,
+    TokenKind::FUNC,
+    TokenKind::STRUCT,
+    TokenKind::IMPORT,
+]);
+// an item keyword inside a block means its closing bracket went missing
+const BLOCK_END: TokenSet = TokenSet::of(&[TokenKind::FUNC, TokenKind::STRUCT, TokenKind::IMPORT, TokenKind::EOI]);
+// panic mode between items stops in front of these. Not `let`: after a broken
+// item it is far more likely a statement of the body than a global
+const ITEM_SYNC: TokenSet = BLOCK_END;
+
+// "expected ..." names these sets as a whole when every kind in one is acceptable
+const EXPECTED_GROUPS: &[(&str, TokenSet)] = &[
+    ("statement", STMT_FIRST),
+    ("expression", EXPR_FIRST),
+    ("type", TYPE_FIRST),
+    ("operator", BINARY_OPERATORS),
+    ("assignment", ASSIGN_OPERATORS),
+];
+
+// names in the Program are interned into `interner`
+pub fn parse(
+    input: &str,
+    file: FileId,
+    config: &LexerConfig,
+    interner: &mut Interner,
+) -> (Program, Vec<ParseError>) {
+    let (tokens, lex_errors) = source::lex_file(input, file, config, interner);
+    parse_tokens(&tokens, &lex_errors, config, interner)
+}
+
+// parses a file that is already lexed, `lex_errors` being the errors lexing
+// went on past. They are reported as syntax errors where lexing hit them
+pub fn parse_tokens(
+    tokens: &[SpannedToken],
+    lex_errors: &[LexError],
+    config: &LexerConfig,
+    interner: &Interner,
+) -> (Program, Vec<ParseError>) {
+    let mut parser = Parser::new(merge_lex_errors(tokens, lex_errors), config, interner);
+    let program = parser.program();
+    (program, parser.errors)
+}
+
+// puts each error back before the first token at or after it. Lexing resumes
+// at or past the position of an error, so that is where it was hit
+fn merge_lex_errors(tokens: &[SpannedToken], lex_errors: &[LexError]) -> Vec<Result<SpannedToken, LexError>> {
+    let mut merged = Vec::with_capacity(tokens.len() + lex_errors.len());
+    let mut errors = lex_errors.iter().peekable();
+    for token in tokens {
+        while let Some(error) = errors.next_if(|error| error.pos <= token.span.start) {
+            merged.push(Err(error.clone()));
+        }
+        merged.push(Ok(token.clone()));
+    }
+    merged.extend(errors.map(|error| Err(error.clone())));
+    merged
+}
+
+pub struct Parser<'a> {
+    tokens: std::vec::IntoIter<Result<SpannedToken, LexError>>,
+    curr: SpannedToken, //EOI until the first bump, and once the tokens run out
+    config: &'a LexerConfig,
+    interner: &'a Interner,
+    prev_end: usize, //end of the last token consumed
+    expected: TokenSet, //kinds the parser checked for since the last token was consumed
+    unspelled: TokenSet, //keywords the dialect removed, they can't turn up so are never listed as expected
+    after_lex_error: bool, //the current token directly follows a lex error
+    errors: Vec<ParseError>,
+}
+
+impl<'a> Parser<'a> {
+    pub fn new(tokens: Vec<Result<SpannedToken, LexError>>, config: &'a LexerConfig, interner: &'a Interner) -> Self {
+        let spelled = config.keywords.values().fold(TokenSet::EMPTY, |set, token| set.with(token.kind()));
+        let keywords = DEFAULT_KEYWORDS.iter().fold(TokenSet::EMPTY, |set, (_, token)| set.with(token.kind()));
+        let mut parser = Parser {
+            tokens: tokens.into_iter(),
+            curr: SpannedToken { token: Token::EOI, span: Span { file: FileId::default(), start: 0, end: 0 } },
+            config,
+            interner,
+            prev_end: 0,
+            expected: TokenSet::EMPTY,
+            unspelled: keywords.minus(spelled),
+            after_lex_error: false,
+            errors: Vec::new(),
+        };
+        parser.bump();
+        parser
+    }
+
+    pub fn program(&mut self) -> Program {
+        let mut items = Vec::new();
+        while !self.check(TokenKind::EOI) {
+            let start = self.span();
+            match self.item() {
+                Ok(item) => items.push(item),
+                Err(Recover) => {
+                    if self.span() == start {
+                        self.bump();
+                    }
+                    while !ITEM_SYNC.contains(self.kind()) {
+                        self.bump();
+                    }
+                }
+            }
+        }
+        Program { items }
+    }
+
+    // ---------------------------------------------------------------------
+    // tokens
+
+    fn curr(&self) -> Token {
+        self.curr.token
+    }
+
+    fn kind(&self) -> TokenKind {
+        self.curr.token.kind()
+    }
+
+    fn span(&self) -> Span {
+        self.curr.span
+    }
+
+    // from `start` to the end of the last token consumed
+    fn span_from(&self, start: Span) -> Span {
+        Span { file: start.file, start: start.start, end: self.prev_end.max(start.start) }
+    }
+
+    // moves to the next token. A lex error on the way is recorded like a
+    // syntax error, lexing went on after the bad character or literal
+    fn bump(&mut self) {
+        self.prev_end = self.curr.span.end;
+        self.expected = TokenSet::EMPTY;
+        self.after_lex_error = false;
+        for next in self.tokens.by_ref() {
+            match next {
+                Ok(token) => {
+                    self.curr = token;
+                    return;
+                }
+                Err(error) => {
+                    let span = Span { file: error.file, start: error.pos, end: error.pos };
+                    self.errors.push(ParseError { message: error.message, span });
+                    self.after_lex_error = true;
+                }
+            }
+        }
+    }
+
+    // consumes the current token
+    fn take(&mut self) -> Token {
+        let token = self.curr();
+        self.bump();
+        token
+    }
+
+    // whether the current token is a `kind`. Either way `kind` is listed as
+    // acceptable if the parser gives up on this token
+    fn check(&mut self, kind: TokenKind) -> bool {
+        self.expected = self.expected.with(kind);
+        self.kind() == kind
+    }
+
+    fn check_any(&mut self, kinds: TokenSet) -> bool {
+        self.expected = self.expected.union(kinds);
+        kinds.contains(self.kind())
+    }
+
+    fn accept(&mut self, kind: TokenKind) -> Option<Token> {
+        if self.check(kind) { Some(self.take()) } else { None }
+    }
+
+    fn accept_any(&mut self, kinds: TokenSet) -> Option<Token> {
+        if self.check_any(kinds) { Some(self.take()) } else { None }
+    }
+
+    fn expect(&mut self, kind: TokenKind) -> Parsed<Token> {
+        if self.check(kind) {
+            return Ok(self.take());
+        }
+        self.unexpected()
+    }
+
+    fn expect_id(&mut self) -> Parsed<Symbol> {
+        match self.expect(TokenKind::ID)? {
+            Token::ID(name) => Ok(name),
+            _ => unreachable!(),
+        }
+    }
+
+    // ---------------------------------------------------------------------
+    // errors
+
+    // reports the current token as not one of the expected ones
+    fn unexpected<T>(&mut self) -> Parsed<T> {
+        // the token the lexer dropped was most likely the one expected here
+        if self.after_lex_error {
+            return Err(Recover);
+        }
+        let message = format!("expected {}, found {}", self.describe_expected(), self.describe(&self.curr()));
+        self.error_at(self.span(), message);
+        Err(Recover)
+    }
+
+    fn error_at(&mut self, span: Span, message: String) {
+        // a missing `]` can be reported by every block it closes
+        if self.errors.last().map(|e| e.span) == Some(span) {
+            return;
+        }
+        self.errors.push(ParseError { message, span });
+    }
+
+    // e.g. "'(', '.', ';' or operator"
+    fn describe_expected(&self) -> String {
+        let mut rest = self.expected.minus(self.unspelled);
+        let mut groups = Vec::new();
+        for (name, kinds) in EXPECTED_GROUPS {
+            let kinds = kinds.minus(self.unspelled);
+            if rest.is_superset(kinds) {
+                rest = rest.minus(kinds);
+                groups.push(name.to_string());
+            }
+        }
+        let mut names: Vec<String> = rest.iter().map(|kind| self.describe_kind(kind)).collect();
+        names.extend(groups);
+        match names.split_last() {
+            None => "nothing".to_string(),
+            Some((last, [])) => last.clone(),
+            Some((last, others)) => format!("{} or {}", others.join(", "), last),
+        }
+    }
+
+    fn describe_kind(&self, kind: TokenKind) -> String {
+        match kind {
+            TokenKind::ID => "identifier".to_string(),
+            TokenKind::LIT_INT32 => "integer literal".to_string(),
+            TokenKind::LIT_FLT32 => "float literal".to_string(),
+            TokenKind::LIT_CHAR => "char literal".to_string(),
+            TokenKind::LIT_STRING => "string literal".to_string(),
+            TokenKind::LIT_BOOL => "bool literal".to_string(),
+            _ => self.describe(&kind.token().unwrap()),
+        }
+    }
+
+    fn describe(&self, token: &Token) -> String {
+        match token {
+            Token::ID(name) => format!("identifier '{}'", self.interner.get(*name)),
+            Token::LIT_INT32(_) | Token::LIT_FLT32(_) | Token::LIT_CHAR(_) | Token::LIT_STRING(_) => {
+                format!("literal {}", unlex::spelling(token, self.interner))
+            }
+            Token::EOI => "end of input".to_string(),
+            _ => format!("'{}'", self.spelling(token)),
+        }
+    }
+
+    // keywords are spelled the way the dialect spells them, the first spelling
+    // alphabetically when it has several so messages don't depend on hash order
+    fn spelling(&self, token: &Token) -> String {
+        let keywords = &self.config.keywords;
+        match keywords.iter().filter(|(_, t)| *t == token).map(|(word, _)| word).min() {
+            Some(word) => word.clone(),
+            None => unlex::spelling(token, self.interner),
+        }
+    }
+
+    // skips to the next statement after an error in one
+    fn synchronize(&mut self) {
+        loop {
+            match self.kind() {
+                TokenKind::SEMICOLON => {
+                    self.bump();
+                    return;
+                }
+                TokenKind::BRACKETS_R | TokenKind::BRACES_R | TokenKind::EOI => return,
+                kind if STATEMENT_KEYWORDS.contains(kind) => return,
+                _ => self.bump(),
+            }
+        }
+    }
+
+    // ---------------------------------------------------------------------
+    // items
+
+    fn item(&mut self) -> Parsed<Item> {
+        let start = self.span();
+        if !self.check_any(ITEM_FIRST) {
+            return self.unexpected();
+        }
+        let kind = match self.kind() {
+            TokenKind::FUNC => self.func()?,
+            TokenKind::STRUCT => self.struct_decl()?,
+            TokenKind::IMPORT => {
+                self.bump();
+                let Token::LIT_STRING(path) = self.expect(TokenKind::LIT_STRING)? else { unreachable!() };
+                self.expect(TokenKind::SEMICOLON)?;
+                ItemKind::Import(path)
+            }
+            _ => ItemKind::Global(self.stmt()?),
+        };
+        Ok(Item { kind, span: self.span_from(start) })
+    }
+
+    fn func(&mut self) -> Parsed<ItemKind> {
+        self.bump();
+        match self.func_header() {
+            Ok((name, params, ret)) => {
+                let body = self.block()?;
+                Ok(ItemKind::Func { name, params, ret, body })
+            }
+            Err(Recover) => {
+                // the body can still be checked after a bad header
+                while !BLOCK_FIRST.union(BLOCK_END).contains(self.kind()) {
+                    self.bump();
+                }
+                if BLOCK_FIRST.contains(self.kind()) {
+                    let _ = self.block();
+                }
+                Err(Recover)
+            }
+        }
+    }
+
+    fn func_header(&mut self) -> Parsed<(Symbol, Vec<Param>, Option<Type>)> {
+        let name = self.expect_id()?;
+        self.expect(TokenKind::PARENS_L)?;
+        let mut params = Vec::new();
+        if !self.check(TokenKind::PARENS_R) {
+            loop {
+                params.push(self.param()?);
+                if self.accept(TokenKind::COMMA).is_none() {
+                    break;
+                }
+            }
+        }
+        self.expect(TokenKind::PARENS_R)?;
+        let ret = if self.accept(TokenKind::ARROW_R).is_some() { Some(self.type_name()?) } else { None };
+        Ok((name, params, ret))
+    }
+
+    // fields are separated by ';' or ',', the last one may go without
+    fn struct_decl(&mut self) -> Parsed<ItemKind> {
+        self.bump();
+        let name = self.expect_id()?;
+        let close = self.open_block()?;
+        let mut fields = Vec::new();
+        while !self.check(close) {
+            fields.push(self.param()?);
+            if self.accept_any(TokenSet::of(&[TokenKind::SEMICOLON, TokenKind::COMMA])).is_none() {
+                break;
+            }
+        }
+        self.expect(close)?;
+        Ok(ItemKind::Struct { name, fields })
+    }
+
+    fn param(&mut self) -> Parsed<Param> {
+        let start = self.span();
+        let name = self.expect_id()?;
+        self.expect(TokenKind::COLON)?;
+        let ty = self.type_name()?;
+        Ok(Param { name, ty, span: self.span_from(start) })
+    }
+
+    fn type_name(&mut self) -> Parsed<Type> {
+        if !self.check_any(TYPE_FIRST) {
+            return self.unexpected();
+        }
+        let ty = match self.take() {
+            Token::TYPE_INT32 => Type::Int32,
+            Token::TYPE_FLT32 => Type::Flt32,
+            Token::TYPE_CHAR => Type::Char,
+            Token::TYPE_STRING => Type::String,
+            Token::TYPE_BOOL => Type::Bool,
+            Token::TYPE_VOID => Type::Void,
+            Token::ID(name) => Type::Named(name),
+            _ => unreachable!(),
+        };
+        Ok(ty)
+    }
+
+    // ---------------------------------------------------------------------
+    // statements
+
+    // consumes `[` or `{` and returns the kind that closes it
+    fn open_block(&mut self) -> Parsed<TokenKind> {
+        match self.accept_any(BLOCK_FIRST) {
+            Some(Token::BRACKETS_L) => Ok(TokenKind::BRACKETS_R),
+            Some(_) => Ok(TokenKind::BRACES_R),
+            None => self.unexpected(),
+        }
+    }
+
+    fn block(&mut self) -> Parsed<Block> {
+        let start = self.span();
+        let close = self.open_block()?;
+        let mut stmts = Vec::new();
+        while !self.check(close) && !BLOCK_END.contains(self.kind()) {
+            let before = self.span();
+            match self.stmt() {
+                Ok(stmt) => stmts.push(stmt),
+                Err(Recover) => self.synchronize(),
+            }
+            if self.span() == before {
+                self.bump();
+            }
+        }
+        self.expect(close)?;
+        Ok(Block { stmts, span: self.span_from(start) })
+    }
+
+    fn stmt(&mut self) -> Parsed<Stmt> {
+        let start = self.span();
+        if !self.check_any(STMT_FIRST) {
+            return self.unexpected();
+        }
+        let kind = match self.kind() {
+            TokenKind::LET => {
+                self.bump();
+                let name = self.expect_id()?;
+                let ty = if self.accept(TokenKind::COLON).is_some() { Some(self.type_name()?) } else { None };
+                let value = if self.accept(TokenKind::ASSIGN).is_some() { Some(self.expr()?) } else { None };
+                self.expect(TokenKind::SEMICOLON)?;
+                StmtKind::Let { name, ty, value }
+            }
+            TokenKind::IF => return self.if_stmt(),
+            TokenKind::WHILE => {
+                self.bump();
+                let cond = self.expr()?;
+                let body = self.block()?;
+                StmtKind::While { cond, body }
+            }
+            TokenKind::RETURN => {
+                self.bump();
+                let value = if self.check(TokenKind::SEMICOLON) { None } else { Some(self.expr()?) };
+                self.expect(TokenKind::SEMICOLON)?;
+                StmtKind::Return(value)
+            }
+            TokenKind::PRINT => {
+                self.bump();
+                let mut args = vec![self.expr()?];
+                while self.accept(TokenKind::COMMA).is_some() {
+                    args.push(self.expr()?);
+                }
+                self.expect(TokenKind::SEMICOLON)?;
+                StmtKind::Print(args)
+            }
+            TokenKind::BRACKETS_L | TokenKind::BRACES_L => StmtKind::Block(self.block()?),
+            _ => {
+                let target = self.expr()?;
+                if let Some(op) = self.accept_any(ASSIGN_OPERATORS) {
+                    if !matches!(target.kind, ExprKind::Var(_) | ExprKind::Field { .. }) {
+                        self.error_at(target.span, "expected a variable or field to assign to".to_string());
+                    }
+                    let value = self.expr()?;
+                    self.expect(TokenKind::SEMICOLON)?;
+                    StmtKind::Assign { target, op, value }
+                } else {
+                    self.expect(TokenKind::SEMICOLON)?;
+                    StmtKind::Expr(target)
+                }
+            }
+        };
+        Ok(Stmt { kind, span: self.span_from(start) })
+    }
+
+    fn if_stmt(&mut self) -> Parsed<Stmt> {
+        let start = self.span();
+        self.bump();
+        let cond = self.expr()?;
+        let then_block = self.block()?;
+        let else_block = if self.accept(TokenKind::ELSE).is_none() {
+            None
+        } else if self.check(TokenKind::IF) {
+            let nested = self.if_stmt()?;
+            Some(Block { span: nested.span, stmts: vec![nested] })
+        } else {
+            Some(self.block()?)
+        };
+        Ok(Stmt { kind: StmtKind::If { cond, then_block, else_block }, span: self.span_from(start) })
+    }
+
+    // ---------------------------------------------------------------------
+    // expressions
+
+    fn expr(&mut self) -> Parsed<Expr> {
+        self.binary(1)
+    }
+
+    // precedence climbing over binary_level
+    fn binary(&mut self, min_level: u8) -> Parsed<Expr> {
+        let mut left = self.unary()?;
+        while self.check_any(BINARY_OPERATORS)
+            && let Some(level) = binary_level(self.kind())
+            && level >= min_level
+        {
+            let op = self.take();
+            let right = self.binary(level + 1)?;
+            let span = Span { file: left.span.file, start: left.span.start, end: right.span.end };
+            left = Expr { kind: ExprKind::Binary { op, left: Box::new(left), right: Box::new(right) }, span };
+        }
+        Ok(left)
+    }
+
+    fn unary(&mut self) -> Parsed<Expr> {
+        let start = self.span();
+        if let Some(op) = self.accept_any(UNARY_OPERATORS) {
+            let operand = self.unary()?;
+            return Ok(Expr { kind: ExprKind::Unary { op, operand: Box::new(operand) }, span: self.span_from(start) });
+        }
+        self.postfix()
+    }
+
+    fn postfix(&mut self) -> Parsed<Expr> {
+        let start = self.span();
+        let mut expr = self.primary()?;
+        loop {
+            if self.accept(TokenKind::PARENS_L).is_some() {
+                let mut args = Vec::new();
+                if !self.check(TokenKind::PARENS_R) {
+                    loop {
+                        args.push(self.expr()?);
+                        if self.accept(TokenKind::COMMA).is_none() {
+                            break;
+                        }
+                    }
+                }
+                self.expect(TokenKind::PARENS_R)?;
+                expr = Expr { kind: ExprKind::Call { callee: Box::new(expr), args }, span: self.span_from(start) };
+            } else if self.accept(TokenKind::POINT).is_some() {
+                let field = self.expect_id()?;
+                expr = Expr { kind: ExprKind::Field { object: Box::new(expr), field }, span: self.span_from(start) };
+            } else {
+                return Ok(expr);
+            }
+        }
+    }
+
+    fn primary(&mut self) -> Parsed<Expr> {
+        let start = self.span();
+        if !self.check_any(PRIMARY_FIRST) {
+            return self.unexpected();
+        }
+        let kind = match self.take() {
+            Token::LIT_INT32(value) => ExprKind::Int(value),
+            Token::LIT_FLT32(value) => ExprKind::Float(value),
+            Token::LIT_CHAR(value) => ExprKind::Char(value),
+            Token::LIT_STRING(value) => ExprKind::Str(value),
+            Token::LIT_BOOL(value) => ExprKind::Bool(value),
+            Token::ID(name) => ExprKind::Var(name),
+            _ => {
+                let inner = self.expr()?;
+                self.expect(TokenKind::PARENS_R)?;
+                return Ok(Expr { kind: inner.kind, span: self.span_from(start) });
+            }
+        };
+        Ok(Expr { kind, span: start })
+    }
+}
+
+// binding strength of each binary operator, higher binds tighter
+fn binary_level(kind: TokenKind) -> Option<u8> {
+    match kind {
+        TokenKind::OR => Some(1),
+        TokenKind::XOR => Some(2),
+        TokenKind::AND => Some(3),
+        TokenKind::EQ | TokenKind::NEQ => Some(4),
+        TokenKind::LT | TokenKind::GT | TokenKind::LE | TokenKind::GE | TokenKind::NLT | TokenKind::NGT => Some(5),
+        TokenKind::ADD | TokenKind::SUB => Some(6),
+        TokenKind::MUL | TokenKind::DIV | TokenKind::MOD => Some(7),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sample_program_parses() {
+        let mut interner = Interner::new();
+        let (program, errors) =
+            parse(include_str!("input.txt"), FileId::default(), &LexerConfig::default(), &mut interner);
+        assert_eq!(errors, vec![]);
+        assert_eq!(program.items.len(), 2);
+        let ItemKind::Func { name, params, ret, body } = &program.items[0].kind else { panic!() };
+        assert_eq!((interner.get(*name), params.len(), ret), ("add", 1, &Some(Type::Int32)));
+        assert_eq!(body.stmts.len(), 3);
+        let StmtKind::Assign { value, .. } = &body.stmts[1].kind else { panic!() };
+        assert!(matches!(&value.kind, ExprKind::Binary { op: Token::ADD, .. }));
+    }
+
+    #[test]
+    fn keywords_with_several_spellings_are_named_the_same_way() {
+        let config =
+            LexerConfig::from_json(r#"{ "keywords": { "add": { "function": "FUNC", "fun": "FUNC", "fn": "FUNC" } } }"#)
+                .unwrap();
+        for input in ["let x = fn;", "let x = function;"] {
+            let (_, errors) = parse(input, FileId::default(), &config, &mut Interner::new());
+            assert_eq!(errors[0].message, "expected expression, found 'fn'");
+        }
+    }
+
+    #[test]
+    fn operators_bind_by_precedence() {
+        let input = "let x = -a + b * c < d || !e;";
+        let (program, _) = parse(input, FileId::default(), &LexerConfig::default(), &mut Interner::new());
+        let ItemKind::Global(Stmt { kind: StmtKind::Let { value: Some(value), .. }, .. }) = &program.items[0].kind
+        else {
+            panic!()
+        };
+        let ExprKind::Binary { op: Token::OR, left, right } = &value.kind else { panic!() };
+        assert!(matches!(right.kind, ExprKind::Unary { op: Token::NOT, .. }));
+        let ExprKind::Binary { op: Token::LT, left, .. } = &left.kind else { panic!() };
+        let ExprKind::Binary { op: Token::ADD, left, right } = &left.kind else { panic!() };
+        assert!(matches!(left.kind, ExprKind::Unary { op: Token::SUB, .. }));
+        assert!(matches!(right.kind, ExprKind::Binary { op: Token::MUL, .. }));
+        assert_eq!((value.span.start, value.span.end), (8, 28));
+    }
+}
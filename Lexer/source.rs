@@ -0,0 +1,371 @@
+// Source files of a multi-file program. Every Span carries the FileId of the
+// file it points into, so errors can name the right file.
+use crate::ast::{self, ItemKind};
+use crate::config::LexerConfig;
+use crate::intern::{Interner, Symbol};
+use crate::parser::{self, ParseError};
+use crate::{LexError, Lexer, Span, SpannedToken, Token};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct FileId(pub usize);
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SourceFile {
+    pub name: String,
+    pub text: String,
+    line_starts: Vec<usize>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SourceMap {
+    files: Vec<SourceFile>,
+}
+
+impl SourceMap {
+    pub fn new() -> Self {
+        SourceMap::default()
+    }
+
+    pub fn add_file(&mut self, name: &str, text: String) -> FileId {
+        let line_starts = std::iter::once(0).chain(text.match_indices('\n').map(|(i, _)| i + 1)).collect();
+        self.files.push(SourceFile { name: name.to_string(), text, line_starts });
+        FileId(self.files.len() - 1)
+    }
+
+    pub fn file(&self, id: FileId) -> &SourceFile {
+        &self.files[id.0]
+    }
+
+    pub fn name(&self, id: FileId) -> &str {
+        &self.files[id.0].name
+    }
+
+    // 1-based line and column, the column counts chars
+    pub fn line_col(&self, id: FileId, pos: usize) -> (usize, usize) {
+        let file = &self.files[id.0];
+        let line = file.line_starts.partition_point(|&start| start <= pos) - 1;
+        let column = file.text[file.line_starts[line]..pos].chars().count();
+        (line + 1, column + 1)
+    }
+
+    // file:line:col
+    pub fn location(&self, file: FileId, pos: usize) -> String {
+        let (line, column) = self.line_col(file, pos);
+        format!("{}:{}:{}", self.name(file), line, column)
+    }
+
+    pub fn describe_lex_error(&self, error: &LexError) -> String {
+        format!("{}: {}", self.location(error.file, error.pos), error.message)
+    }
+
+    pub fn describe(&self, span: Span, message: &str) -> String {
+        format!("{}: {}", self.location(span.file, span.start), message)
+    }
+}
+
+// ---------------------------------------------------------------------------
+// import loading
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ProgramFile {
+    pub id: FileId,
+    pub tokens: Vec<SpannedToken>,
+    pub imports: Vec<FileId>,
+    pub lex_errors: Vec<LexError>, //lexing skipped past these, parsing reports them again
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Program {
+    pub sources: SourceMap,
+    pub files: Vec<ProgramFile>, //imported files come before the files importing them
+    pub interner: Interner, //the names in every file's tokens
+}
+
+// `import "path";` statements in a token vector, paths are relative to the file
+pub fn find_imports(tokens: &[SpannedToken]) -> Result<Vec<(Symbol, Span)>, (Span, String)> {
+    let mut imports = Vec::new();
+    for (i, token) in tokens.iter().enumerate() {
+        if token.token != Token::IMPORT {
+            continue;
+        }
+        let path = match tokens.get(i + 1) {
+            Some(SpannedToken { token: Token::LIT_STRING(path), span }) => (*path, *span),
+            Some(other) => return Err((other.span, "Expected a file name string after import".to_string())),
+            None => return Err((token.span, "Expected a file name string after import".to_string())),
+        };
+        match tokens.get(i + 2) {
+            Some(SpannedToken { token: Token::SEMICOLON, .. }) => imports.push(path),
+            Some(other) => return Err((other.span, "Expected ';' after import".to_string())),
+            None => return Err((path.1, "Expected ';' after import".to_string())),
+        }
+    }
+    Ok(imports)
+}
+
+// lexes a whole file, going on after each error, so the imports of a file with
+// a bad literal are still found and the parser can report every error
+pub fn lex_file(
+    text: &str,
+    file: FileId,
+    config: &LexerConfig,
+    interner: &mut Interner,
+) -> (Vec<SpannedToken>, Vec<LexError>) {
+    let mut lexer = Lexer::with_config(config.clone());
+    lexer.set_file(file);
+    lexer.set_interner(std::mem::take(interner));
+    lexer.reset(text.to_string(), 0);
+    let mut tokens = Vec::new();
+    let mut errors = Vec::new();
+    loop {
+        match lexer.try_advance() {
+            Ok(token) => {
+                tokens.push(SpannedToken { token, span: lexer.curr_span() });
+                if token == Token::EOI {
+                    break;
+                }
+            }
+            Err(error) => {
+                lexer.skip_error(&error);
+                errors.push(error);
+            }
+        }
+    }
+    *interner = lexer.take_interner();
+    (tokens, errors)
+}
+
+struct Loader<'a> {
+    config: &'a LexerConfig,
+    sources: SourceMap,
+    by_path: HashMap<PathBuf, FileId>,
+    loading: Vec<FileId>, //files whose imports are still being loaded
+    modules: HashMap<String, FileId>, //imported files by module name
+    files: Vec<ProgramFile>,
+    interner: Interner,
+}
+
+impl Loader<'_> {
+    fn load(&mut self, path: &Path, imported_at: Option<Span>) -> Result<FileId, String> {
+        let fail = |sources: &SourceMap, message: String| match imported_at {
+            Some(span) => sources.describe(span, &message),
+            None => message,
+        };
+        let text = fs::read_to_string(path)
+            .map_err(|error| fail(&self.sources, format!("Error reading file {}: {}", path.display(), error)))?;
+        let key = fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+
+        if let Some(&id) = self.by_path.get(&key) {
+            if let Some(index) = self.loading.iter().position(|&f| f == id) {
+                let mut cycle: Vec<&str> = self.loading[index..].iter().map(|&f| self.sources.name(f)).collect();
+                cycle.push(self.sources.name(id));
+                return Err(fail(&self.sources, format!("Import cycle: {}", cycle.join(" -> "))));
+            }
+            return Ok(id);
+        }
+
+        let id = self.sources.add_file(&path.display().to_string(), text);
+        self.by_path.insert(key, id);
+        // the functions of an imported file are named by its module, two
+        // modules of the same name would share their functions
+        if imported_at.is_some() {
+            let name = module_name(self.sources.name(id));
+            if let Some(&other) = self.modules.get(&name) {
+                let message = format!(
+                    "Module name {} is used by both {} and {}",
+                    name,
+                    self.sources.name(other),
+                    self.sources.name(id)
+                );
+                return Err(fail(&self.sources, message));
+            }
+            self.modules.insert(name, id);
+        }
+        self.loading.push(id);
+
+        let text = &self.sources.file(id).text;
+        let (tokens, lex_errors) = lex_file(text, id, self.config, &mut self.interner);
+        let found = find_imports(&tokens).map_err(|(span, message)| self.sources.describe(span, &message))?;
+        let directory = path.parent().unwrap_or(Path::new(""));
+        let mut imports = Vec::new();
+        for (relative, span) in found {
+            let relative = self.interner.get(relative).to_string();
+            imports.push(self.load(&directory.join(relative), Some(span))?);
+        }
+
+        self.loading.pop();
+        self.files.push(ProgramFile { id, tokens, imports, lex_errors });
+        Ok(id)
+    }
+}
+
+// lexes `root` and every file it imports, directly or not. Only files that
+// can't be read or imported fail the load, lex errors are kept with their file
+pub fn load_program(root: &str, config: &LexerConfig) -> Result<Program, String> {
+    let mut loader = Loader {
+        config,
+        sources: SourceMap::new(),
+        by_path: HashMap::new(),
+        loading: Vec::new(),
+        modules: HashMap::new(),
+        files: Vec::new(),
+        interner: Interner::new(),
+    };
+    loader.load(Path::new(root), None)?;
+    Ok(Program { sources: loader.sources, files: loader.files, interner: loader.interner })
+}
+
+// the file name without the extension, which importers call its functions by
+fn module_name(file_name: &str) -> String {
+    Path::new(file_name).file_stem().unwrap_or_default().to_string_lossy().into_owned()
+}
+
+// parses every file of a loaded program into one syntax tree, imported files
+// first, from the tokens load_program kept. A function of an imported file is
+// named the way importers call it, <module>.<name>, so two files can each have
+// their own `add`; load_program makes sure module names are unique. Calls
+// inside a module still use the plain name, resolving those is left to a later
+// name resolution pass
+pub fn parse_program(program: &mut Program, config: &LexerConfig) -> (ast::Program, Vec<ParseError>) {
+    let root = program.files.last().map(|file| file.id);
+    let mut items = Vec::new();
+    let mut errors = Vec::new();
+    for file in &program.files {
+        let (parsed, file_errors) = parser::parse_tokens(&file.tokens, &file.lex_errors, config, &program.interner);
+        errors.extend(file_errors);
+        let module = module_name(program.sources.name(file.id));
+        for mut item in parsed.items {
+            if let ItemKind::Func { name, .. } = &mut item.kind
+                && Some(file.id) != root
+            {
+                *name = program.interner.intern(&format!("{}.{}", module, program.interner.get(*name)));
+            }
+            items.push(item);
+        }
+    }
+    (ast::Program { items }, errors)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write_files(test: &str, files: &[(&str, &str)]) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("lexer-imports-{}-{}", test, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        for (name, text) in files {
+            let path = dir.join(name);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, text).unwrap();
+        }
+        dir
+    }
+
+    #[test]
+    fn imports_load_in_dependency_order() {
+        let dir = write_files(
+            "order",
+            &[
+                ("main.src", "import \"lib/math.src\";\nimport \"util.src\";\nfunc main() [ ]"),
+                ("util.src", "import \"lib/math.src\";"),
+                ("lib/math.src", "func add(x : int32) -> int32 [ return x; ]"),
+            ],
+        );
+        let program = load_program(dir.join("main.src").to_str().unwrap(), &LexerConfig::default()).unwrap();
+        let names: Vec<&str> = program.files.iter().map(|f| program.sources.name(f.id)).collect();
+        assert_eq!(names.len(), 3);
+        assert!(names[0].ends_with("math.src"));
+        assert!(names[1].ends_with("util.src"));
+        assert!(names[2].ends_with("main.src"));
+        // math.src is lexed once and shared
+        assert_eq!(program.files[2].imports, vec![program.files[0].id, program.files[1].id]);
+        assert!(program.files[0].tokens.iter().all(|t| t.span.file == program.files[0].id));
+    }
+
+    #[test]
+    fn import_errors_name_the_right_file() {
+        let dir = write_files(
+            "cycle",
+            &[("a.src", "import \"b.src\";"), ("b.src", "\n  import \"a.src\";"), ("c.src", "let x = 1;\nlet s = \"open")],
+        );
+        let (a, b) = (dir.join("a.src").display().to_string(), dir.join("b.src").display().to_string());
+        let error = load_program(&a, &LexerConfig::default()).unwrap_err();
+        assert_eq!(error, format!("{}:2:10: Import cycle: {} -> {} -> {}", b, a, b, a));
+
+        let program = load_program(dir.join("c.src").to_str().unwrap(), &LexerConfig::default()).unwrap();
+        let error = program.sources.describe_lex_error(&program.files[0].lex_errors[0]);
+        assert!(error.ends_with("c.src:2:9: Unterminated string literal"), "{}", error);
+
+        let dir = write_files("missing", &[("main.src", "import \"gone.src\";"), ("bad.src", "import gone;")]);
+        let error = load_program(dir.join("main.src").to_str().unwrap(), &LexerConfig::default()).unwrap_err();
+        assert!(error.contains("main.src:1:8: Error reading file"), "{}", error);
+        let error = load_program(dir.join("bad.src").to_str().unwrap(), &LexerConfig::default()).unwrap_err();
+        assert!(error.ends_with("bad.src:1:8: Expected a file name string after import"), "{}", error);
+    }
+
+    #[test]
+    fn imported_functions_are_named_by_module() {
+        let dir = write_files(
+            "modules",
+            &[
+                ("main.src", "import \"lib/math.src\";\nfunc add() [ print math.add(1); ]"),
+                ("lib/math.src", "func add(x : int32) -> int32 [ return x +; ]"),
+            ],
+        );
+        let mut program = load_program(dir.join("main.src").to_str().unwrap(), &LexerConfig::default()).unwrap();
+        let (ast, errors) = parse_program(&mut program, &LexerConfig::default());
+        let names: Vec<&str> = ast
+            .items
+            .iter()
+            .filter_map(|item| match &item.kind {
+                ItemKind::Func { name, .. } => Some(program.interner.get(*name)),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(names, vec!["math.add", "add"]);
+        assert_eq!(errors.len(), 1);
+        let error = program.sources.describe(errors[0].span, &errors[0].message);
+        assert!(error.ends_with("math.src:1:42: expected expression, found ';'"), "{}", error);
+    }
+
+    #[test]
+    fn modules_with_the_same_name_are_an_error() {
+        let dir = write_files(
+            "same-name",
+            &[
+                ("main.src", "import \"lib/util.src\";\nimport \"app/util.src\";"),
+                ("lib/util.src", "func add() [ ]"),
+                ("app/util.src", "func add() [ ]"),
+                ("ok.src", "import \"lib/util.src\";\nimport \"app/../lib/util.src\";"),
+            ],
+        );
+        let error = load_program(dir.join("main.src").to_str().unwrap(), &LexerConfig::default()).unwrap_err();
+        let (lib, app) = (dir.join("lib/util.src"), dir.join("app/util.src"));
+        let expected = format!("main.src:2:8: Module name util is used by both {} and {}", lib.display(), app.display());
+        assert!(error.ends_with(&expected), "{}", error);
+        // the same file imported twice is one module
+        assert!(load_program(dir.join("ok.src").to_str().unwrap(), &LexerConfig::default()).is_ok());
+    }
+
+    #[test]
+    fn lex_errors_are_reported_among_syntax_errors() {
+        let text = "func f() [\n  let = 3;\n  let a = 1 # 2;\n  let s = \"open\n  let = 'xy'; ]";
+        let dir = write_files("lex-errors", &[("main.src", text)]);
+        let mut program = load_program(dir.join("main.src").to_str().unwrap(), &LexerConfig::default()).unwrap();
+        let (_, errors) = parse_program(&mut program, &LexerConfig::default());
+        let errors: Vec<String> = errors.iter().map(|e| program.sources.describe(e.span, &e.message)).collect();
+        let errors: Vec<&str> = errors.iter().map(|e| e.rsplit("main.src:").next().unwrap()).collect();
+        assert_eq!(
+            errors,
+            vec![
+                "2:7: expected identifier, found '='",
+                "3:13: Unexpected character: '#'",
+                "4:11: Unterminated string literal",
+                "5:7: expected identifier, found '='",
+                "5:9: Character literal must contain exactly one character",
+            ]
+        );
+    }
+}
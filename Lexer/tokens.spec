@@ -0,0 +1,66 @@
+# Token definitions for the lexer generator (lexgen.rs).
+# Each line is: NAME PRIORITY PATTERN
+# The longest match wins; on equal length the higher priority wins, then the
+# earlier line. `skip` matches are thrown away.
+
+skip        0   \s+
+
+# keywords beat identifiers of the same length
+FUNC        2   func
+LET         2   let
+IF          2   if
+ELSE        2   else
+WHILE       2   while
+PRINT       2   print
+RETURN      2   return
+STRUCT      2   struct
+IMPORT      2   import
+TYPE_INT32  2   int32
+TYPE_FLT32  2   flt32
+TYPE_CHAR   2   char
+TYPE_STRING 2   string
+TYPE_BOOL   2   bool
+TYPE_VOID   2   void
+LIT_BOOL    2   true|false
+ID          1   [A-Za-z_][A-Za-z0-9_]*
+
+# a '.' is only part of a float when a digit follows it
+LIT_INT32   1   [0-9]+
+LIT_FLT32   1   [0-9]+\.[0-9]+
+LIT_CHAR    1   '([^'\\]|\\[^xu]|\\x[0-9A-Fa-f]{2}|\\u\{[0-9A-Fa-f]{1,6}\})'
+LIT_STRING  1   "([^"\\]|\\[^xu]|\\x[0-9A-Fa-f]{2}|\\u\{[0-9A-Fa-f]{1,6}\})*"
+
+PARENS_L    1   \(
+PARENS_R    1   \)
+BRACKETS_L  1   \[
+BRACKETS_R  1   \]
+BRACES_L    1   \{
+BRACES_R    1   \}
+POINT       1   \.
+COMMA       1   ,
+COLON       1   :
+SEMICOLON   1   ;
+ARROW_R     1   ->
+
+ADD         1   \+
+SUB         1   -
+MUL         1   \*
+DIV         1   /
+MOD         1   %
+EQ          1   ==
+LT          1   <
+GT          1   >
+NEQ         1   !=
+NLT         1   !<
+NGT         1   !>
+LE          1   <=
+GE          1   >=
+NOT         1   !
+AND         1   &&
+OR          1   \|\|
+XOR         1   \^
+ASSIGN      1   =
+ADD_ASSIGN  1   \+=
+SUB_ASSIGN  1   -=
+MUL_ASSIGN  1   \*=
+DIV_ASSIGN  1   /=
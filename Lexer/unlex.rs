@@ -0,0 +1,269 @@
+// Turns tokens back into source text that lexes to the same tokens. The lexer
+// never makes a negative or NaN literal, `-` is a token of its own, so those
+// have no such text. spelling still writes them the way they read, for dumps
+// and messages, and unlex rejects them.
+use crate::Token;
+use crate::intern::Interner;
+
+const OPERATORS: &[&str] = &[
+    "->", "+", "-", "*", "/", "%", "==", "<", ">", "!=", "!<", "!>", "<=", ">=", "!", "&&", "||", "^",
+    "=", "+=", "-=", "*=", "/=",
+];
+
+pub fn spelling(token: &Token, interner: &Interner) -> String {
+    let text = match token {
+        Token::PARENS_L => "(",
+        Token::PARENS_R => ")",
+        Token::BRACKETS_L => "[",
+        Token::BRACKETS_R => "]",
+        Token::BRACES_L => "{",
+        Token::BRACES_R => "}",
+        Token::POINT => ".",
+        Token::COMMA => ",",
+        Token::COLON => ":",
+        Token::SEMICOLON => ";",
+        Token::ARROW_R => "->",
+        Token::ADD => "+",
+        Token::SUB => "-",
+        Token::MUL => "*",
+        Token::DIV => "/",
+        Token::MOD => "%",
+        Token::EQ => "==",
+        Token::LT => "<",
+        Token::GT => ">",
+        Token::NEQ => "!=",
+        Token::NLT => "!<",
+        Token::NGT => "!>",
+        Token::LE => "<=",
+        Token::GE => ">=",
+        Token::NOT => "!",
+        Token::AND => "&&",
+        Token::OR => "||",
+        Token::XOR => "^",
+        Token::ASSIGN => "=",
+        Token::ADD_ASSIGN => "+=",
+        Token::SUB_ASSIGN => "-=",
+        Token::MUL_ASSIGN => "*=",
+        Token::DIV_ASSIGN => "/=",
+        Token::FUNC => "func",
+        Token::LET => "let",
+        Token::IF => "if",
+        Token::ELSE => "else",
+        Token::WHILE => "while",
+        Token::PRINT => "print",
+        Token::RETURN => "return",
+        Token::STRUCT => "struct",
+        Token::IMPORT => "import",
+        Token::TYPE_INT32 => "int32",
+        Token::TYPE_FLT32 => "flt32",
+        Token::TYPE_CHAR => "char",
+        Token::TYPE_STRING => "string",
+        Token::TYPE_BOOL => "bool",
+        Token::TYPE_VOID => "void",
+        Token::LIT_BOOL(true) => "true",
+        Token::LIT_BOOL(false) => "false",
+        Token::EOI => "",
+        Token::ID(name) => return interner.get(*name).to_string(),
+        Token::LIT_INT32(value) => return value.to_string(),
+        Token::LIT_FLT32(value) => return float_spelling(*value),
+        Token::LIT_CHAR(c) => return format!("'{}'", escape(*c, '\'')),
+        Token::LIT_STRING(s) => {
+            let body: String = interner.get(*s).chars().map(|c| escape(c, '"')).collect();
+            return format!("\"{}\"", body);
+        }
+    };
+    text.to_string()
+}
+
+// Display already prints the shortest digits that parse back to the same f32,
+// but leaves off the point for whole numbers which would lex as LIT_INT32.
+// Infinity is what a literal too big for f32 lexes to, so it is spelled as one.
+fn float_spelling(value: f32) -> String {
+    if value.is_infinite() {
+        let sign = if value < 0.0 { "-" } else { "" };
+        return format!("{}1{}.0", sign, "0".repeat(39));
+    }
+    let text = value.to_string();
+    if text.contains('.') || value.is_nan() { text } else { format!("{}.0", text) }
+}
+
+// true if the lexer can produce `token`, so its spelling lexes back to it
+pub fn round_trips(token: &Token) -> bool {
+    match token {
+        Token::LIT_INT32(value) => *value >= 0,
+        Token::LIT_FLT32(value) => !value.is_nan() && value.is_sign_positive(),
+        _ => true,
+    }
+}
+
+fn escape(c: char, quote: char) -> String {
+    match c {
+        '\n' => "\\n".to_string(),
+        '\t' => "\\t".to_string(),
+        '\r' => "\\r".to_string(),
+        '\0' => "\\0".to_string(),
+        '\\' => "\\\\".to_string(),
+        c if c == quote => format!("\\{}", c),
+        c if c.is_control() => format!("\\u{{{:X}}}", c as u32),
+        c => c.to_string(),
+    }
+}
+
+fn is_word(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '_'
+}
+
+// true if writing `next` straight after `prev` would lex differently
+fn would_merge(prev: &Token, prev_text: &str, next_text: &str) -> bool {
+    let (Some(last), Some(first)) = (prev_text.chars().last(), next_text.chars().next()) else {
+        return false;
+    };
+    if is_word(last) && is_word(first) {
+        return true;
+    }
+    // `1.` followed by a digit would become a float
+    if matches!(prev, Token::LIT_INT32(_)) && first == '.' {
+        return true;
+    }
+    let joined = format!("{}{}", prev_text, first);
+    OPERATORS.iter().any(|op| op.starts_with(&joined))
+}
+
+// punctuation that reads better without a space, as long as nothing merges
+fn glued(prev: &Token, next: &Token) -> bool {
+    matches!(prev, Token::PARENS_L | Token::POINT)
+        || matches!(next, Token::PARENS_R | Token::COMMA | Token::SEMICOLON | Token::POINT)
+        || matches!((prev, next), (Token::ID(_), Token::PARENS_L))
+}
+
+pub fn unlex(tokens: &[Token], interner: &Interner) -> Result<String, String> {
+    if let Some(token) = tokens.iter().find(|token| !round_trips(token)) {
+        return Err(format!("{} has no spelling that lexes back to it", interner.debug(token)));
+    }
+    let mut out = String::new();
+    let mut prev: Option<(&Token, String)> = None;
+    for token in tokens {
+        if *token == Token::EOI {
+            break;
+        }
+        let text = spelling(token, interner);
+        if let Some((prev_token, prev_text)) = &prev {
+            if **prev_token == Token::SEMICOLON {
+                out.push('\n');
+            } else if !glued(prev_token, token) || would_merge(prev_token, prev_text, &text) {
+                out.push(' ');
+            }
+        }
+        out.push_str(&text);
+        prev = Some((token, text));
+    }
+    Ok(out)
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::Lexer;
+    use crate::fuzz::Rng;
+
+    fn lex(input: &str, interner: &mut Interner) -> Vec<Token> {
+        let mut tokens: Vec<Token> =
+            Lexer::tokenize(input, interner).unwrap().into_iter().map(|t| t.token).collect();
+        assert_eq!(tokens.pop(), Some(Token::EOI));
+        tokens
+    }
+
+    fn random_char(rng: &mut Rng) -> char {
+        let pool = ['a', 'Z', '0', ' ', '"', '\'', '\\', '\n', '\t', '\r', '\0', '\u{7}', 'é', '😀', '{', '}'];
+        pool[rng.below(pool.len())]
+    }
+
+    pub(crate) fn random_token(rng: &mut Rng, interner: &mut Interner) -> Token {
+        use Token::*;
+        let fixed = [
+            PARENS_L, PARENS_R, BRACKETS_L, BRACKETS_R, BRACES_L, BRACES_R, POINT, COMMA, COLON,
+            SEMICOLON, ARROW_R, ADD, SUB, MUL, DIV, MOD, EQ, LT, GT, NEQ, NLT, NGT, LE, GE, NOT, AND,
+            OR, XOR, ASSIGN, ADD_ASSIGN, SUB_ASSIGN, MUL_ASSIGN, DIV_ASSIGN, FUNC, LET, IF, ELSE,
+            WHILE, PRINT, RETURN, STRUCT, IMPORT, TYPE_INT32, TYPE_FLT32, TYPE_CHAR, TYPE_STRING, TYPE_BOOL,
+            TYPE_VOID, LIT_BOOL(true), LIT_BOOL(false),
+        ];
+        match rng.below(6) {
+            0 => {
+                let names = ["x", "_", "a1", "value", "func_", "iff", "int3", "x_y"];
+                ID(interner.intern(names[rng.below(names.len())]))
+            }
+            // any i32 and any f32, including ones unlex has to reject
+            1 => LIT_INT32(rng.next() as u32 as i32 >> rng.below(31)),
+            2 => {
+                let specials = [f32::INFINITY, f32::NEG_INFINITY, f32::NAN, -0.0, f32::MAX];
+                LIT_FLT32(match rng.below(8) {
+                    0 => specials[rng.below(specials.len())],
+                    _ => f32::from_bits(rng.next() as u32),
+                })
+            }
+            3 => LIT_CHAR(random_char(rng)),
+            4 => {
+                let text: String = (0..rng.below(5)).map(|_| random_char(rng)).collect();
+                LIT_STRING(interner.intern(&text))
+            }
+            _ => fixed[rng.below(fixed.len())],
+        }
+    }
+
+    #[test]
+    fn lex_of_unlex_is_identity() {
+        let mut rng = Rng(0x9E3779B97F4A7C15);
+        let mut interner = Interner::new();
+        for _ in 0..3000 {
+            let tokens: Vec<Token> = (0..rng.below(12)).map(|_| random_token(&mut rng, &mut interner)).collect();
+            match unlex(&tokens, &interner) {
+                Ok(text) => assert_eq!(lex(&text, &mut interner), tokens, "unlexed as {:?}", text),
+                Err(_) => assert!(!tokens.iter().all(round_trips), "{:?}", tokens),
+            }
+        }
+    }
+
+    #[test]
+    fn literals_the_lexer_cant_make_are_rejected() {
+        let interner = Interner::new();
+        for token in [
+            Token::LIT_INT32(-1),
+            Token::LIT_INT32(i32::MIN),
+            Token::LIT_FLT32(-1.5),
+            Token::LIT_FLT32(-0.0),
+            Token::LIT_FLT32(f32::NEG_INFINITY),
+            Token::LIT_FLT32(f32::NAN),
+        ] {
+            assert!(unlex(&[Token::LET, token], &interner).is_err(), "{:?}", token);
+        }
+        let error = unlex(&[Token::LIT_FLT32(f32::NAN)], &interner).unwrap_err();
+        assert_eq!(error, "LIT_FLT32(NaN) has no spelling that lexes back to it");
+        // spelling still writes them for dumps and messages
+        assert_eq!(spelling(&Token::LIT_FLT32(f32::NAN), &interner), "NaN");
+        assert_eq!(spelling(&Token::LIT_INT32(-3), &interner), "-3");
+    }
+
+    #[test]
+    fn infinity_is_spelled_as_a_literal_too_big_for_f32() {
+        let mut interner = Interner::new();
+        let text = unlex(&[Token::LIT_FLT32(f32::INFINITY)], &interner).unwrap();
+        assert!(!text.contains("inf"), "{}", text);
+        assert_eq!(lex(&text, &mut interner), vec![Token::LIT_FLT32(f32::INFINITY)]);
+        assert_eq!(lex(&float_spelling(f32::MAX), &mut interner), vec![Token::LIT_FLT32(f32::MAX)]);
+    }
+
+    #[test]
+    fn sample_program_round_trips() {
+        let mut interner = Interner::new();
+        let tokens = lex(
+            "func add(x : int32) -> int32 [ let v : flt32 = 1.0; print \"a\\\"b\", '\\n'; return 1 .5; ]",
+            &mut interner,
+        );
+        let text = unlex(&tokens, &interner).unwrap();
+        assert_eq!(lex(&text, &mut interner), tokens);
+        assert_eq!(
+            text,
+            "func add(x : int32) -> int32 [ let v : flt32 = 1.0;\nprint \"a\\\"b\", '\\n';\nreturn 1 .5;\n]"
+        );
+    }
+}